@@ -0,0 +1,83 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// Interpreter throughput on the bundled ROMs, with and without the decoded instruction cache, and on the blocks backend.
+// Run with `cargo bench`, e.g. `cargo bench -- pong` for a single ROM.
+
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::BatchSize;
+use criterion::Criterion;
+use criterion::Throughput;
+
+use rs_8chip_interpret::interpreter::cpu::Backend;
+use rs_8chip_interpret::interpreter::cpu::CPU;
+use rs_8chip_interpret::interpreter::font::Font;
+use rs_8chip_interpret::interpreter::keypad::Keypad;
+use rs_8chip_interpret::interpreter::memory::Memory;
+use rs_8chip_interpret::interpreter::platform::Platform;
+use rs_8chip_interpret::interpreter::quirks::Quirks;
+
+const INSTRUCTIONS: u64 = 100_000;
+
+const ROMS: [(&str, &[u8]); 5] = [
+    ("ibm", include_bytes!("../roms/ibm.ch8")),
+    ("test_opcode", include_bytes!("../roms/test_opcode.ch8")),
+    ("corax+", include_bytes!("../roms/3-corax+.ch8")),
+    ("1dcell", include_bytes!("../roms/1dcell.ch8")),
+    ("pong", include_bytes!("../roms/pong.rom")),
+];
+
+fn setup(rom: &[u8], cache: bool, backend: Backend) -> (CPU, Memory, Keypad) {
+    let map = Platform::OriginalChip8.memory_map();
+    let mut memory = Memory::new(map);
+    memory.initialize(&Font::new());
+    memory.load_rom(rom).unwrap();
+
+    // Without display wait, otherwise drawing ROMs would mostly measure waiting.
+    let mut quirks = Quirks::from_preset("chip8").unwrap();
+    quirks.set("display-wait", false);
+    let mut cpu = CPU::new(&map);
+    cpu.set_quirks(quirks);
+    cpu.set_instruction_cache(cache);
+    cpu.set_backend(backend);
+    return (cpu, memory, Keypad::new());
+}
+
+fn run((mut cpu, mut memory, mut keypad): (CPU, Memory, Keypad)) -> CPU {
+    cpu.run(&mut memory, &mut keypad, INSTRUCTIONS as u32).unwrap();
+    return cpu;
+}
+
+fn interpreter(c: &mut Criterion) {
+    for (name, rom) in ROMS.iter() {
+        let mut group = c.benchmark_group(*name);
+        group.throughput(Throughput::Elements(INSTRUCTIONS));
+        group.bench_function("decode", |b| b.iter_batched(|| setup(rom, false, Backend::Interpreter), run, BatchSize::LargeInput));
+        group.bench_function("cached", |b| b.iter_batched(|| setup(rom, true, Backend::Interpreter), run, BatchSize::LargeInput));
+        group.bench_function("blocks", |b| b.iter_batched(|| setup(rom, false, Backend::Blocks), run, BatchSize::LargeInput));
+        group.finish();
+    }
+}
+
+criterion_group!(benches, interpreter);
+criterion_main!(benches);
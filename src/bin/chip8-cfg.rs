@@ -0,0 +1,87 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// Exports the control-flow graph of a CHIP-8 ROM (basic blocks with their disassembly, jump, call,
+// skip and jump table edges) as Graphviz DOT or JSON.
+
+use std::fs;
+
+use clap::Parser;
+use clap::ValueEnum;
+
+use rs_8chip_interpret::interpreter::cfg::ControlFlowGraph;
+use rs_8chip_interpret::interpreter::database::RomDatabase;
+use rs_8chip_interpret::interpreter::platform::Platform;
+use rs_8chip_interpret::interpreter::platform::PLATFORMS;
+use rs_8chip_interpret::interpreter::rom::RomLoader;
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Format {
+    Dot,
+    Json,
+}
+
+#[derive(Parser)]
+#[command(name = "chip8-cfg", version, about = "Control-flow graph export for CHIP-8 ROMs")]
+struct Options {
+    /// ROM file, in any format the interpreter loads
+    rom: String,
+
+    /// Output format [default: json if the output path ends in .json, dot otherwise]
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+
+    /// Write to a file instead of stdout
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<String>,
+
+    /// Platform, which decides the load address [default: from the ROM database, or originalChip8]
+    #[arg(long, value_name = "ID", value_parser = PLATFORMS)]
+    platform: Option<String>,
+}
+
+fn main() {
+    let options = Options::parse();
+    let rom = match RomLoader::new().load(&options.rom) {
+        Ok(rom) => rom,
+        Err(error) => {
+            eprintln!("{}: failed to load ROM: {}", options.rom, error);
+            std::process::exit(1);
+        },
+    };
+    let platform = options.platform.as_deref().and_then(Platform::from_id)
+        .or(RomDatabase::new().identify(&rom).map(|settings| settings.platform))
+        .unwrap_or(Platform::OriginalChip8);
+
+    let graph = ControlFlowGraph::new(&rom, platform.memory_map().load_address);
+    let json = options.output.as_deref().is_some_and(|path| path.ends_with(".json"));
+    let output = match options.format {
+        Some(Format::Json) => graph.to_json(),
+        Some(Format::Dot) => graph.to_dot(),
+        None if json => graph.to_json(),
+        None => graph.to_dot(),
+    };
+    match options.output {
+        Some(path) => fs::write(&path, output).expect("Failed to write the control-flow graph."),
+        None => print!("{}", output),
+    }
+}
@@ -0,0 +1,85 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// Static analyzer for CHIP-8 ROMs: walks the code reachable from the load address and reports
+// invalid and platform specific instructions, quirk-sensitive instructions, recursion, reads of
+// uninitialized memory, jumps into data and blocking key waits.
+
+use clap::Parser;
+
+use rs_8chip_interpret::interpreter::database::RomDatabase;
+use rs_8chip_interpret::interpreter::lint::lint;
+use rs_8chip_interpret::interpreter::lint::Severity;
+use rs_8chip_interpret::interpreter::platform::Platform;
+use rs_8chip_interpret::interpreter::platform::PLATFORMS;
+use rs_8chip_interpret::interpreter::rom::RomLoader;
+
+#[derive(Parser)]
+#[command(name = "chip8-lint", version, about = "Static analyzer for CHIP-8 ROMs")]
+struct Options {
+    /// ROM files to check, in any format the interpreter loads
+    #[arg(required = true)]
+    roms: Vec<String>,
+
+    /// Platform to check against [default: from the ROM database, or originalChip8]
+    #[arg(long, value_name = "ID", value_parser = PLATFORMS)]
+    platform: Option<String>,
+
+    /// Leave out notes, only report errors and warnings
+    #[arg(long)]
+    no_notes: bool,
+}
+
+fn main() {
+    let options = Options::parse();
+    let database = RomDatabase::new();
+    let mut failed = false;
+
+    for path in options.roms.iter() {
+        let rom = match RomLoader::new().load(path) {
+            Ok(rom) => rom,
+            Err(error) => {
+                eprintln!("{}: failed to load ROM: {}", path, error);
+                failed = true;
+                continue;
+            },
+        };
+        let platform = options.platform.as_deref().and_then(Platform::from_id)
+            .or(database.identify(&rom).map(|settings| settings.platform))
+            .unwrap_or(Platform::OriginalChip8);
+
+        let diagnostics = lint(&rom, platform);
+        let mut counts = [0; 3];
+        for diagnostic in diagnostics.iter().filter(|diagnostic| !options.no_notes || diagnostic.severity != Severity::Note) {
+            println!("{}:0x{:03X}: {}: {}", path, diagnostic.address, diagnostic.severity, diagnostic.message);
+            println!("    suggestion: {}", diagnostic.suggestion);
+            counts[diagnostic.severity as usize] += 1;
+        }
+        println!("{}: {} errors, {} warnings, {} notes ({})", path, counts[0], counts[1], counts[2], platform.id());
+        failed |= counts[0] > 0;
+    }
+
+    // Errors fail the run, so it can gate a build.
+    if failed {
+        std::process::exit(1);
+    }
+}
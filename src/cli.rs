@@ -0,0 +1,524 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::fs;
+use std::path::Path;
+
+use clap::error::ErrorKind;
+use clap::CommandFactory;
+use clap::Parser;
+use serde::Deserialize;
+
+use crate::interpreter::audio::Tone;
+use crate::interpreter::audio::Waveform;
+use crate::interpreter::bench::parse_limit;
+use crate::interpreter::bench::Limit;
+use crate::interpreter::bench::DEFAULT_LIMIT;
+use crate::interpreter::cpu::Backend;
+use crate::interpreter::cpu::BACKENDS;
+use crate::interpreter::display::parse_palette;
+use crate::interpreter::display::Palette;
+use crate::interpreter::display::DEFAULT_SCALE_FACTOR;
+use crate::interpreter::font::Font;
+use crate::interpreter::font::FONTS;
+use crate::interpreter::memory::BoundsPolicy;
+use crate::interpreter::memory::ReservedAccess;
+use crate::interpreter::memory::BOUNDS_POLICIES;
+use crate::interpreter::memory::RESERVED_ACCESS;
+use crate::interpreter::platform::Platform;
+use crate::interpreter::platform::PLATFORMS;
+use crate::interpreter::quirks::Quirks;
+use crate::interpreter::quirks::PRESETS;
+use crate::interpreter::quirks::QUIRKS;
+use crate::interpreter::random::GENERATORS;
+use crate::interpreter::watcher::Reload;
+use crate::interpreter::watcher::RELOADS;
+
+const DEFAULT_CONFIG: &str = "config.toml";
+const WAVEFORMS: [&str; 4] = ["square", "triangle", "sawtooth", "sine"];
+
+// Every option can also be set in a TOML config file (same names, e.g. `ips = 15` or
+// `palette = "000000,ffffff"`), options given on the command line take precedence.
+#[derive(Parser, Deserialize, Default)]
+#[command(name = "rs-8chip-interpret", version, about = "CHIP-8 interpreter")]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct Options {
+    /// ROM file to run: a binary, hex dump or zip archive, "-" reads from stdin
+    rom: Option<String>,
+
+    /// Read the ROM as a hex text dump, whatever its name [default: only .hex and .txt files are]
+    #[arg(long)]
+    hex: bool,
+
+    /// Config file [default: config.toml, if present]
+    #[arg(long, value_name = "PATH")]
+    #[serde(skip)]
+    config: Option<String>,
+
+    /// Instructions executed per frame, at 60 frames per second [default: from the ROM database, or 9]
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..=100_000))]
+    ips: Option<u32>,
+
+    /// Quirks preset [default: from the platform, or the interpreter's original behaviour]
+    #[arg(long, value_name = "PRESET", value_parser = PRESETS)]
+    quirks: Option<String>,
+
+    /// Turn a single quirk on or off, e.g. display-wait=off (vf-reset, memory, shift, jump, display-wait, clip)
+    #[arg(long, value_name = "QUIRK=on|off", value_parser = quirk_arg)]
+    quirk: Vec<String>,
+
+    /// Platform, sets the quirks and speed [default: from the ROM database]
+    #[arg(long, value_name = "ID", value_parser = PLATFORMS)]
+    platform: Option<String>,
+
+    /// Address programs are loaded at and start from, in hex [default: from the platform, 200 (600 on the ETI-660)]
+    #[arg(long, value_name = "ADDRESS", value_parser = address_arg)]
+    load_address: Option<String>,
+
+    /// Address of the font, in hex [default: 000]
+    #[arg(long, value_name = "ADDRESS", value_parser = address_arg)]
+    font_address: Option<String>,
+
+    /// Access to the interpreter region below the load address: read/write, read-only or none [default: rw]
+    #[arg(long, value_name = "ACCESS", value_parser = RESERVED_ACCESS)]
+    reserved: Option<String>,
+
+    /// Accesses past the end of RAM: wrap I around, mirror RAM, or halt with a fault [default: from the platform]
+    #[arg(long, value_name = "POLICY", value_parser = BOUNDS_POLICIES)]
+    bounds: Option<String>,
+
+    /// Nested calls before a stack overflow, or "unlimited" [default: from the platform, or 16]
+    #[arg(long, value_name = "N", value_parser = stack_depth_arg)]
+    stack_depth: Option<String>,
+
+    /// Keep the call stack in RAM (growing down from 0xED0) like the COSMAC VIP
+    #[arg(long)]
+    stack_in_ram: bool,
+
+    /// Font [default: from the platform, or octo]
+    #[arg(long, value_name = "NAME", value_parser = FONTS, conflicts_with = "font_file")]
+    font: Option<String>,
+
+    /// Custom font: the 80 bytes of the small font, optionally followed by 10 or 16 big (8x10) glyphs
+    #[arg(long, value_name = "PATH")]
+    font_file: Option<String>,
+
+    /// Directory with a checkout of the community chip-8-database [default: bundled database]
+    #[arg(long, value_name = "DIR")]
+    rom_db: Option<String>,
+
+    /// Per-ROM overrides of the ROM database, keyed by SHA-1 [default: rom-overrides.json, if present]
+    #[arg(long, value_name = "PATH")]
+    rom_overrides: Option<String>,
+
+    /// Window scale factor [default: 10]
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..=64))]
+    scale: Option<u32>,
+
+    /// Background and foreground colours [default: from the ROM database, or 000000,ffffff]
+    #[arg(long, value_name = "BG,FG", value_parser = palette_arg)]
+    palette: Option<String>,
+
+    /// Run without a window, audio device or any input, as fast as possible (needs --frames or --replay)
+    #[arg(long)]
+    headless: bool,
+
+    /// Exit after N frames
+    #[arg(long, value_name = "N")]
+    frames: Option<u64>,
+
+    /// Benchmark the ROM headless for a duration (--bench=10s) or a number of instructions, then exit [default: 5s]
+    #[arg(long, value_name = "LENGTH", num_args = 0..=1, require_equals = true, default_missing_value = DEFAULT_LIMIT, value_parser = bench_arg)]
+    #[serde(skip)]
+    bench: Option<String>,
+
+    /// Execution backend: one instruction at a time, or blocks of pre-decoded instructions [default: interpreter]
+    #[arg(long, value_name = "BACKEND", value_parser = BACKENDS)]
+    backend: Option<String>,
+
+    /// Reload the ROM when the file changes, resetting the CPU or only patching the changed bytes (--watch=patch) [default: reset]
+    #[arg(long, value_name = "MODE", num_args = 0..=1, require_equals = true, default_missing_value = "reset", value_parser = RELOADS)]
+    watch: Option<String>,
+
+    /// Print every executed instruction and the registers to stderr, and the accesses that wrap past the end of RAM
+    #[arg(long)]
+    trace: bool,
+
+    /// Seed for the random number generator used by Cxnn [default: random]
+    #[arg(long, value_name = "N")]
+    seed: Option<u64>,
+
+    /// Random number generator used by Cxnn [default: default]
+    #[arg(long, value_name = "NAME", value_parser = GENERATORS)]
+    rng: Option<String>,
+
+    /// Load a save state before starting
+    #[arg(long, value_name = "PATH")]
+    load_state: Option<String>,
+
+    /// Key bindings [default: keymap.toml, if present]
+    #[arg(long, value_name = "PATH")]
+    keymap: Option<String>,
+
+    /// Record frames to a GIF (.gif), raw RGB24 file, or stdout (-)
+    #[arg(long, value_name = "PATH")]
+    record: Option<String>,
+
+    /// Record the seed and every frame's input to a movie file
+    #[arg(long, value_name = "PATH")]
+    record_movie: Option<String>,
+
+    /// Replay a movie file and verify its final framebuffer
+    #[arg(long, value_name = "PATH")]
+    replay: Option<String>,
+
+    /// Beep frequency in Hz [default: 440]
+    #[arg(long, value_name = "HZ", value_parser = frequency_arg)]
+    tone: Option<f32>,
+
+    /// Beep volume between 0.0 and 1.0 [default: 0.25]
+    #[arg(long, value_name = "VOLUME", value_parser = volume_arg)]
+    volume: Option<f32>,
+
+    /// Beep waveform [default: square]
+    #[arg(long, value_name = "WAVEFORM", value_parser = WAVEFORMS)]
+    waveform: Option<String>,
+
+    /// Write all emulated audio to a WAV file
+    #[arg(long, value_name = "PATH")]
+    wav: Option<String>,
+
+    /// Profile the ROM, write the cycles per address and subroutine and an annotated disassembly on exit
+    #[arg(long, value_name = "PATH")]
+    profile: Option<String>,
+
+    /// Profile the ROM, write its call stacks in the collapsed format of flame graph tools on exit
+    #[arg(long, value_name = "PATH")]
+    profile_stacks: Option<String>,
+
+    /// Write which ROM bytes were executed, read or written and which way every skip went on exit (HTML for .html, text otherwise)
+    #[arg(long, value_name = "PATH")]
+    coverage: Option<String>,
+}
+
+// Options the ROM database can also provide are left as None when they weren't given.
+pub struct Settings {
+    pub rom: String,
+    pub ips: Option<u32>,
+    pub quirks: Option<Quirks>,
+    // Applied over the quirks from --quirks, the platform or the ROM database.
+    pub quirk: Vec<(String, bool)>,
+    pub platform: Option<Platform>,
+    pub load_address: Option<u16>,
+    pub font_address: Option<u16>,
+    pub reserved: Option<ReservedAccess>,
+    pub bounds: Option<BoundsPolicy>,
+    // Some(None) is an unlimited stack.
+    pub stack_depth: Option<Option<usize>>,
+    pub stack_in_ram: bool,
+    pub hex: bool,
+    pub font: Option<Font>,
+    pub rom_db: Option<String>,
+    pub rom_overrides: Option<String>,
+    pub scale: u32,
+    pub palette: Option<Palette>,
+    pub headless: bool,
+    pub frames: Option<u64>,
+    pub bench: Option<Limit>,
+    pub backend: Backend,
+    pub watch: Option<Reload>,
+    pub trace: bool,
+    pub seed: Option<u64>,
+    pub rng: String,
+    pub load_state: Option<String>,
+    pub keymap: Option<String>,
+    pub record: Option<String>,
+    pub record_movie: Option<String>,
+    pub replay: Option<String>,
+    pub tone: Tone,
+    pub wav: Option<String>,
+    pub profile: Option<String>,
+    pub profile_stacks: Option<String>,
+    pub coverage: Option<String>,
+}
+
+// Parses the command line, merges it over the config file and validates the result.
+// Errors are reported the same way clap reports them and exit the process.
+pub fn parse() -> Settings {
+    let cli = Options::parse();
+
+    let config_path = cli.config.clone().unwrap_or(DEFAULT_CONFIG.to_string());
+    let config = if cli.config.is_some() || Path::new(&config_path).exists() {
+        let content = fs::read_to_string(&config_path)
+            .unwrap_or_else(|error| fail(ErrorKind::Io, format!("failed to read config file {}: {}", config_path, error)));
+        toml::from_str::<Options>(&content)
+            .unwrap_or_else(|error| fail(ErrorKind::InvalidValue, format!("invalid config file {}: {}", config_path, error)))
+    } else {
+        Options::default()
+    };
+
+    let options = Options {
+        rom: cli.rom.or(config.rom),
+        config: None,
+        ips: cli.ips.or(config.ips),
+        quirks: cli.quirks.or(config.quirks),
+        // Both apply, the command line last.
+        quirk: config.quirk.into_iter().chain(cli.quirk).collect(),
+        platform: cli.platform.or(config.platform),
+        load_address: cli.load_address.or(config.load_address),
+        font_address: cli.font_address.or(config.font_address),
+        reserved: cli.reserved.or(config.reserved),
+        bounds: cli.bounds.or(config.bounds),
+        stack_depth: cli.stack_depth.or(config.stack_depth),
+        stack_in_ram: cli.stack_in_ram || config.stack_in_ram,
+        hex: cli.hex || config.hex,
+        font: cli.font.or(config.font),
+        font_file: cli.font_file.or(config.font_file),
+        rom_db: cli.rom_db.or(config.rom_db),
+        rom_overrides: cli.rom_overrides.or(config.rom_overrides),
+        scale: cli.scale.or(config.scale),
+        palette: cli.palette.or(config.palette),
+        headless: cli.headless || config.headless,
+        frames: cli.frames.or(config.frames),
+        bench: cli.bench,
+        backend: cli.backend.or(config.backend),
+        watch: cli.watch.or(config.watch),
+        trace: cli.trace || config.trace,
+        seed: cli.seed.or(config.seed),
+        rng: cli.rng.or(config.rng),
+        load_state: cli.load_state.or(config.load_state),
+        keymap: cli.keymap.or(config.keymap),
+        record: cli.record.or(config.record),
+        record_movie: cli.record_movie.or(config.record_movie),
+        replay: cli.replay.or(config.replay),
+        tone: cli.tone.or(config.tone),
+        volume: cli.volume.or(config.volume),
+        waveform: cli.waveform.or(config.waveform),
+        wav: cli.wav.or(config.wav),
+        profile: cli.profile.or(config.profile),
+        profile_stacks: cli.profile_stacks.or(config.profile_stacks),
+        coverage: cli.coverage.or(config.coverage),
+    };
+
+    // Values from the config file haven't been through clap's validation yet.
+    let rom = options.rom.unwrap_or_else(|| fail(ErrorKind::MissingRequiredArgument, "no ROM file given".to_string()));
+    if let Some(ips) = options.ips.filter(|ips| !(1..=100_000).contains(ips)) {
+        fail(ErrorKind::ValueValidation, format!("ips must be between 1 and 100000, got {}", ips));
+    }
+    let scale = options.scale.unwrap_or(DEFAULT_SCALE_FACTOR);
+    if !(1..=64).contains(&scale) {
+        fail(ErrorKind::ValueValidation, format!("scale must be between 1 and 64, got {}", scale));
+    }
+    let quirks = options.quirks.as_deref().map(|name| {
+        Quirks::from_preset(name)
+            .unwrap_or_else(|| fail(ErrorKind::InvalidValue, format!("unknown quirks preset \"{}\", expected one of {:?}", name, PRESETS)))
+    });
+    let quirk = options.quirk.iter()
+        .map(|quirk| parse_quirk(quirk).unwrap_or_else(|error| fail(ErrorKind::ValueValidation, error)))
+        .collect();
+    let platform = options.platform.as_deref().map(|id| {
+        Platform::from_id(id)
+            .unwrap_or_else(|| fail(ErrorKind::InvalidValue, format!("unknown platform \"{}\", expected one of {:?}", id, PLATFORMS)))
+    });
+    let load_address = options.load_address.as_deref().map(|address| {
+        parse_address(address).unwrap_or_else(|error| fail(ErrorKind::ValueValidation, error))
+    });
+    let font_address = options.font_address.as_deref().map(|address| {
+        parse_address(address).unwrap_or_else(|error| fail(ErrorKind::ValueValidation, error))
+    });
+    let reserved = options.reserved.as_deref().map(|name| {
+        ReservedAccess::from_name(name)
+            .unwrap_or_else(|| fail(ErrorKind::InvalidValue, format!("unknown reserved access \"{}\", expected one of {:?}", name, RESERVED_ACCESS)))
+    });
+    let bounds = options.bounds.as_deref().map(|name| {
+        BoundsPolicy::from_name(name)
+            .unwrap_or_else(|| fail(ErrorKind::InvalidValue, format!("unknown bounds policy \"{}\", expected one of {:?}", name, BOUNDS_POLICIES)))
+    });
+    let stack_depth = options.stack_depth.as_deref().map(|depth| {
+        parse_stack_depth(depth).unwrap_or_else(|error| fail(ErrorKind::ValueValidation, error))
+    });
+    if options.font.is_some() && options.font_file.is_some() {
+        fail(ErrorKind::ArgumentConflict, "--font can't be combined with --font-file".to_string());
+    }
+    let font = match (options.font.as_deref(), options.font_file.as_deref()) {
+        (Some(name), _) => Some(Font::from_name(name)
+            .unwrap_or_else(|| fail(ErrorKind::InvalidValue, format!("unknown font \"{}\", expected one of {:?}", name, FONTS)))),
+        (None, Some(path)) => Some(Font::load(path).unwrap_or_else(|error| fail(ErrorKind::Io, error))),
+        (None, None) => None,
+    };
+    let palette = options.palette.as_deref().map(|palette| {
+        parse_palette(palette).unwrap_or_else(|error| fail(ErrorKind::ValueValidation, error))
+    });
+    let bench = options.bench.as_deref().map(|limit| parse_limit(limit).unwrap_or_else(|error| fail(ErrorKind::ValueValidation, error)));
+    let backend = options.backend.as_deref().map(|name| {
+        Backend::from_name(name)
+            .unwrap_or_else(|| fail(ErrorKind::InvalidValue, format!("unknown backend \"{}\", expected one of {:?}", name, BACKENDS)))
+    }).unwrap_or(Backend::Interpreter);
+    let watch = options.watch.as_deref().map(|name| {
+        Reload::from_name(name)
+            .unwrap_or_else(|| fail(ErrorKind::InvalidValue, format!("unknown watch mode \"{}\", expected one of {:?}", name, RELOADS)))
+    });
+    if watch.is_some() && rom == "-" {
+        fail(ErrorKind::ArgumentConflict, "--watch needs a ROM file, stdin can't be watched".to_string());
+    }
+    let rng = options.rng.unwrap_or("default".to_string());
+    if !GENERATORS.contains(&rng.as_str()) {
+        fail(ErrorKind::InvalidValue, format!("unknown random generator \"{}\", expected one of {:?}", rng, GENERATORS));
+    }
+
+    let mut tone = Tone::new();
+    if let Some(frequency) = options.tone {
+        tone.frequency = check_frequency(frequency).unwrap_or_else(|error| fail(ErrorKind::ValueValidation, error));
+    }
+    if let Some(volume) = options.volume {
+        tone.volume = check_volume(volume).unwrap_or_else(|error| fail(ErrorKind::ValueValidation, error));
+    }
+    if let Some(waveform) = options.waveform.as_deref() {
+        tone.waveform = Waveform::from_name(waveform)
+            .unwrap_or_else(|| fail(ErrorKind::InvalidValue, format!("unknown waveform \"{}\", expected one of {:?}", waveform, WAVEFORMS)));
+    }
+
+    if options.replay.is_some() && options.record_movie.is_some() {
+        fail(ErrorKind::ArgumentConflict, "--replay can't be combined with --record-movie".to_string());
+    }
+    // Without a window there's nothing to close, headless runs need something that ends them.
+    if options.headless && options.frames.is_none() && options.replay.is_none() && bench.is_none() {
+        fail(ErrorKind::MissingRequiredArgument, "--headless needs --frames N (or --replay), it would never exit otherwise".to_string());
+    }
+
+    return Settings {
+        rom,
+        ips: options.ips,
+        quirks,
+        quirk,
+        platform,
+        load_address,
+        font_address,
+        reserved,
+        bounds,
+        stack_depth,
+        stack_in_ram: options.stack_in_ram,
+        hex: options.hex,
+        font,
+        rom_db: options.rom_db,
+        rom_overrides: options.rom_overrides,
+        scale,
+        palette,
+        // Benchmarks never open a window.
+        headless: options.headless || bench.is_some(),
+        frames: options.frames,
+        bench,
+        backend,
+        watch,
+        trace: options.trace,
+        seed: options.seed,
+        rng,
+        load_state: options.load_state,
+        keymap: options.keymap,
+        record: options.record,
+        record_movie: options.record_movie,
+        replay: options.replay,
+        tone,
+        wav: options.wav,
+        profile: options.profile,
+        profile_stacks: options.profile_stacks,
+        coverage: options.coverage,
+    };
+}
+
+fn fail(kind: ErrorKind, message: String) -> ! {
+    Options::command().error(kind, message).exit();
+}
+
+// Hex, with or without a "0x" prefix.
+fn parse_address(value: &str) -> Result<u16, String> {
+    let digits = value.strip_prefix("0x").unwrap_or(value);
+    return u16::from_str_radix(digits, 16).map_err(|_| format!("\"{}\" isn't a hex address", value));
+}
+
+fn address_arg(value: &str) -> Result<String, String> {
+    return parse_address(value).map(|_| value.to_string());
+}
+
+fn parse_quirk(value: &str) -> Result<(String, bool), String> {
+    let invalid = || format!("quirks are set with <quirk>=on|off, one of {:?}, got \"{}\"", QUIRKS, value);
+    let (name, enabled) = value.split_once('=').ok_or_else(invalid)?;
+    let enabled = match enabled {
+        "on" => true,
+        "off" => false,
+        _ => return Err(invalid()),
+    };
+    if !QUIRKS.contains(&name) {
+        return Err(invalid());
+    }
+    return Ok((name.to_string(), enabled));
+}
+
+fn quirk_arg(value: &str) -> Result<String, String> {
+    return parse_quirk(value).map(|_| value.to_string());
+}
+
+fn parse_stack_depth(value: &str) -> Result<Option<usize>, String> {
+    if value == "unlimited" {
+        return Ok(None);
+    }
+    match value.parse::<usize>() {
+        Ok(depth) if depth > 0 => Ok(Some(depth)),
+        _ => Err(format!("stack depth must be a positive number or \"unlimited\", got \"{}\"", value)),
+    }
+}
+
+fn stack_depth_arg(value: &str) -> Result<String, String> {
+    return parse_stack_depth(value).map(|_| value.to_string());
+}
+
+fn bench_arg(value: &str) -> Result<String, String> {
+    return parse_limit(value).map(|_| value.to_string());
+}
+
+fn palette_arg(value: &str) -> Result<String, String> {
+    return parse_palette(value).map(|_| value.to_string());
+}
+
+fn check_frequency(frequency: f32) -> Result<f32, String> {
+    if !(20.0..=20000.0).contains(&frequency) {
+        return Err(format!("tone must be a frequency between 20 and 20000 Hz, got {}", frequency));
+    }
+    return Ok(frequency);
+}
+
+fn frequency_arg(value: &str) -> Result<f32, String> {
+    let frequency = value.parse::<f32>().map_err(|_| format!("\"{}\" isn't a number", value))?;
+    return check_frequency(frequency);
+}
+
+fn check_volume(volume: f32) -> Result<f32, String> {
+    if !(0.0..=1.0).contains(&volume) {
+        return Err(format!("volume must be between 0.0 and 1.0, got {}", volume));
+    }
+    return Ok(volume);
+}
+
+fn volume_arg(value: &str) -> Result<f32, String> {
+    let volume = value.parse::<f32>().map_err(|_| format!("\"{}\" isn't a number", value))?;
+    return check_volume(volume);
+}
@@ -0,0 +1,192 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+
+use super::cpu::Instructions;
+use super::platform::extension;
+
+// Static analysis of a ROM: the code reachable from the load address and how control flows through it.
+// Targets of Bnnn depend on a register, only jump tables (runs of 1nnn at nnn) are followed.
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EdgeKind {
+    // To the next instruction.
+    Next,
+    Jump,
+    Call,
+    // Over the next instruction, when a 3xnn, 4xnn, 5xy0, 9xy0, Ex9E or ExA1 condition holds.
+    Skip,
+    // From Bnnn to an entry of its jump table.
+    Table,
+}
+
+impl EdgeKind {
+    pub fn name(&self) -> &str {
+        match self {
+            EdgeKind::Next => "next",
+            EdgeKind::Jump => "jump",
+            EdgeKind::Call => "call",
+            EdgeKind::Skip => "skip",
+            EdgeKind::Table => "table",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Edge {
+    pub from: u16,
+    pub to: u16,
+    pub kind: EdgeKind,
+}
+
+pub struct Analysis {
+    pub load_address: u16,
+    pub rom: Vec<u8>,
+    // Reachable instructions by address, including undecodable ones.
+    pub code: BTreeMap<u16, u16>,
+    pub edges: BTreeSet<Edge>,
+    // Edges leading outside the ROM, they aren't followed.
+    pub outside: BTreeSet<Edge>,
+    // Entry points of the subroutines called with 2nnn.
+    pub subroutines: BTreeSet<u16>,
+}
+
+pub fn is_skip(instruction: Instructions) -> bool {
+    return matches!(instruction,
+        Instructions::Instruction3xnn | Instructions::Instruction4xnn | Instructions::Instruction5xy0 |
+        Instructions::Instruction9xy0 | Instructions::Instructionex9e | Instructions::Instructionexa1);
+}
+
+impl Analysis {
+    pub fn new(rom: &[u8], load_address: u16) -> Self {
+        let mut analysis = Analysis {
+            load_address,
+            rom: rom.to_vec(),
+            code: BTreeMap::new(),
+            edges: BTreeSet::new(),
+            outside: BTreeSet::new(),
+            subroutines: BTreeSet::new(),
+        };
+        analysis.explore();
+        return analysis;
+    }
+
+    pub fn in_rom(&self, address: u16) -> bool {
+        let start = self.load_address as usize;
+        return (start..start + self.rom.len()).contains(&(address as usize));
+    }
+
+    pub fn opcode_at(&self, address: u16) -> Option<u16> {
+        if !self.in_rom(address) || !self.in_rom(address.wrapping_add(1)) {
+            return None;
+        }
+        let offset = (address - self.load_address) as usize;
+        return Some(u16::from_be_bytes([self.rom[offset], self.rom[offset + 1]]));
+    }
+
+    // Where control can go after the instruction at `address`.
+    pub fn successors(&self, address: u16, opcode: u16) -> Vec<(u16, EdgeKind)> {
+        let next = address.wrapping_add(2);
+        let nnn = opcode & 0x0FFF;
+        let Some(instruction) = Instructions::decode(opcode) else {
+            // SUPER-CHIP and XO-CHIP instructions continue with the next one, except for exit.
+            if extension(opcode).is_some() && opcode != 0x00FD {
+                return vec![(next, EdgeKind::Next)];
+            }
+            return Vec::new();
+        };
+        return match instruction {
+            Instructions::Instruction00ee => Vec::new(),
+            Instructions::Instruction1nnn => vec![(nnn, EdgeKind::Jump)],
+            // Execution continues after the call once the subroutine returns.
+            Instructions::Instruction2nnn => vec![(nnn, EdgeKind::Call), (next, EdgeKind::Next)],
+            Instructions::Instructionbnnn => {
+                let mut entries = Vec::new();
+                let mut entry = nnn;
+                while self.opcode_at(entry).is_some_and(|opcode| opcode & 0xF000 == 0x1000) {
+                    entries.push((entry, EdgeKind::Table));
+                    entry = entry.wrapping_add(2);
+                }
+                entries
+            },
+            instruction if is_skip(instruction) => vec![(next, EdgeKind::Next), (next.wrapping_add(2), EdgeKind::Skip)],
+            _ => vec![(next, EdgeKind::Next)],
+        };
+    }
+
+    fn explore(&mut self) {
+        let mut pending = vec![self.load_address];
+        while let Some(address) = pending.pop() {
+            if self.code.contains_key(&address) {
+                continue;
+            }
+            let Some(opcode) = self.opcode_at(address) else {
+                continue;
+            };
+            self.code.insert(address, opcode);
+            for (to, kind) in self.successors(address, opcode) {
+                let edge = Edge { from: address, to, kind };
+                if self.opcode_at(to).is_none() {
+                    self.outside.insert(edge);
+                    continue;
+                }
+                if kind == EdgeKind::Call {
+                    self.subroutines.insert(to);
+                }
+                self.edges.insert(edge);
+                pending.push(to);
+            }
+        }
+    }
+
+    pub fn edges_from(&self, address: u16) -> impl Iterator<Item = &Edge> {
+        return self.edges.range(Edge { from: address, to: 0, kind: EdgeKind::Next }..)
+            .take_while(move |edge| edge.from == address);
+    }
+
+    // Instructions of the subroutine (or main program) starting at `entry`, without the subroutines it calls.
+    pub fn body(&self, entry: u16) -> BTreeSet<u16> {
+        let mut body = BTreeSet::new();
+        let mut pending = vec![entry];
+        while let Some(address) = pending.pop() {
+            if !self.code.contains_key(&address) || !body.insert(address) {
+                continue;
+            }
+            pending.extend(self.edges_from(address).filter(|edge| edge.kind != EdgeKind::Call).map(|edge| edge.to));
+        }
+        return body;
+    }
+
+    // Subroutines called from the subroutine (or main program) starting at `entry`.
+    pub fn callees(&self, entry: u16) -> BTreeSet<u16> {
+        return self.body(entry).iter()
+            .filter_map(|address| self.code.get(address).filter(|opcode| *opcode & 0xF000 == 0x2000))
+            .map(|opcode| opcode & 0x0FFF)
+            .filter(|target| self.subroutines.contains(target))
+            .collect();
+    }
+}
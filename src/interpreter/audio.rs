@@ -0,0 +1,207 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::BufWriter;
+
+use sdl2::audio::AudioQueue;
+use sdl2::audio::AudioSpecDesired;
+use sdl2::Sdl;
+
+const SAMPLE_RATE: i32 = 44100;
+const FRAME_RATE: i32 = 60;
+// If the queue grows beyond this many frames of audio (e.g. the window was dragged), drop it to stay in sync.
+const MAX_QUEUED_FRAMES: u32 = 4;
+
+#[derive(Clone, Copy)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sawtooth,
+    Sine,
+}
+
+impl Waveform {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "square" => Some(Waveform::Square),
+            "triangle" => Some(Waveform::Triangle),
+            "sawtooth" => Some(Waveform::Sawtooth),
+            "sine" => Some(Waveform::Sine),
+            _ => None,
+        }
+    }
+
+    // phase is within [0, 1)
+    fn sample(&self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+            Waveform::Triangle => 4.0 * (phase - 0.5).abs() - 1.0,
+            Waveform::Sawtooth => 2.0 * phase - 1.0,
+            Waveform::Sine => (phase * 2.0 * PI).sin(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Tone {
+    pub frequency: f32,
+    pub volume: f32,
+    pub waveform: Waveform,
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+impl Tone {
+    pub fn new() -> Self {
+        Self {
+            frequency: 440.0,
+            volume: 0.25,
+            waveform: Waveform::Square,
+        }
+    }
+}
+
+pub struct Audio {
+    device: Option<AudioQueue<f32>>,
+    tone: Tone,
+    sample_rate: i32,
+    frames: u64,
+    phase: f32,
+    active: bool,
+    muted: bool,
+    samples: Vec<f32>,
+    wav: Option<hound::WavWriter<BufWriter<File>>>,
+}
+
+impl Audio {
+    // Falls back to the null backend if there's no usable audio device.
+    pub fn new(sdl_context: &Sdl, tone: Tone) -> Self {
+        let desired = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: None,
+        };
+
+        let device: AudioQueue<f32> = match sdl_context.audio().and_then(|audio| audio.open_queue(None, &desired)) {
+            Ok(device) => device,
+            Err(error) => {
+                eprintln!("Failed to open audio device ({}), continuing without sound.", error);
+                return Self::new_null(tone);
+            }
+        };
+        device.resume();
+
+        let sample_rate = device.spec().freq;
+        let mut audio = Self::new_null(tone);
+        audio.device = Some(device);
+        audio.sample_rate = sample_rate;
+        audio
+    }
+
+    // Produces the same samples as the SDL backend without opening an audio device, used for headless runs.
+    pub fn new_null(tone: Tone) -> Self {
+        Self {
+            device: None,
+            tone,
+            sample_rate: SAMPLE_RATE,
+            frames: 0,
+            phase: 0.0,
+            active: false,
+            muted: false,
+            samples: Vec::new(),
+            wav: None,
+        }
+    }
+
+    // Writes every sample produced from now on to a 16-bit mono WAV file, regardless of mute or audio device.
+    // Each frame is exactly sample_rate / 60 samples long so beeps can be matched to frames.
+    pub fn export_wav(&mut self, path: &str) {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: self.sample_rate as u32,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        self.wav = Some(hound::WavWriter::create(path, spec).expect("Failed to create WAV file."));
+    }
+
+    pub fn finish(self) {
+        if let Some(wav) = self.wav {
+            wav.finalize().expect("Failed to finalize WAV file.");
+        }
+    }
+
+    pub fn toggle_mute(&mut self) -> bool {
+        self.muted = !self.muted;
+        return self.muted;
+    }
+
+    // Called once per 60Hz tick, the tone is either on or off for the whole frame so it starts and stops
+    // exactly on frame boundaries.
+    pub fn update(&mut self, active: bool) {
+        // Spread the remainder when the sample rate isn't divisible by 60.
+        let start = self.frames * self.sample_rate as u64 / FRAME_RATE as u64;
+        let end = (self.frames + 1) * self.sample_rate as u64 / FRAME_RATE as u64;
+        self.frames += 1;
+
+        // Every beep starts at the same point of the waveform.
+        if active && !self.active {
+            self.phase = 0.0;
+        }
+        self.active = active;
+
+        self.samples.clear();
+        let step = self.tone.frequency / self.sample_rate as f32;
+        for _ in start..end {
+            if active {
+                self.samples.push(self.tone.waveform.sample(self.phase) * self.tone.volume);
+                self.phase = (self.phase + step).fract();
+            } else {
+                self.samples.push(0.0);
+            }
+        }
+
+        if let Some(wav) = self.wav.as_mut() {
+            for sample in self.samples.iter() {
+                wav.write_sample((sample * i16::MAX as f32) as i16).expect("Failed to write WAV samples.");
+            }
+        }
+
+        if let Some(device) = self.device.as_ref() {
+            let frame_size = (self.samples.len() * std::mem::size_of::<f32>()) as u32;
+            if device.size() > frame_size * MAX_QUEUED_FRAMES {
+                device.clear();
+            }
+            if self.muted {
+                device.queue_audio(&vec![0.0; self.samples.len()]).unwrap();
+            } else {
+                device.queue_audio(&self.samples).unwrap();
+            }
+        }
+    }
+}
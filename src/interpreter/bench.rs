@@ -0,0 +1,169 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::fmt;
+use std::time::Duration;
+use std::time::Instant;
+
+use super::cpu::CpuState;
+use super::cpu::Fault;
+use super::cpu::CPU;
+use super::keypad::Keypad;
+use super::memory::Memory;
+use super::random::RandomSource;
+
+const FRAME_RATE: f64 = 60.0;
+
+// Opcode families by their first nibble.
+const FAMILIES: [&str; 16] = [
+    "00E0/00EE", "1nnn", "2nnn", "3xnn", "4xnn", "5xy0", "6xnn", "7xnn",
+    "8xyN", "9xy0", "Annn", "Bnnn", "Cxnn", "Dxyn", "ExNN", "FxNN",
+];
+
+// How long a benchmark runs, in wall-clock time or emulated instructions.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Limit {
+    Time(Duration),
+    Cycles(u64),
+}
+
+pub const DEFAULT_LIMIT: &str = "5s";
+
+// "10s" or "2.5s" is a duration, a plain number an instruction count.
+pub fn parse_limit(value: &str) -> Result<Limit, String> {
+    let limit = match value.strip_suffix('s') {
+        Some(seconds) => seconds.parse::<f64>().ok()
+            .filter(|seconds| *seconds > 0.0 && seconds.is_finite())
+            .map(|seconds| Limit::Time(Duration::from_secs_f64(seconds))),
+        None => value.parse::<u64>().ok().filter(|cycles| *cycles > 0).map(Limit::Cycles),
+    };
+    return limit.ok_or(format!("benchmark length must be a duration in seconds (e.g. \"10s\") or a number of instructions, got \"{}\"", value));
+}
+
+// Executed instructions and time spent on them, per opcode family.
+pub type FamilyProfile = [(u64, Duration); 16];
+
+pub struct Report {
+    ips: u32,
+    elapsed: Duration,
+    cycles: u64,
+    frames: u64,
+    // From a second pass, timing every instruction slows it down too much to measure throughput at the same time.
+    // The cost of the timing itself is subtracted.
+    families: FamilyProfile,
+}
+
+// Runs the loaded ROM headless with no input, no sleeping and no presentation, then again with
+// every instruction timed for the per-family breakdown. Both passes start from the state the ROM
+// was in when called, random source included, so they run the same instructions.
+pub fn run(cpu: &mut CPU, memory: &mut Memory, keypad: &mut Keypad, ips: u32, limit: Limit) -> Result<Report, Fault> {
+    let state = cpu.get_state();
+    let ram = memory.get_ram().to_vec();
+    let rng = cpu.get_random_source();
+    let (elapsed, cycles, frames) = pass(cpu, memory, keypad, ips, limit)?;
+    restore(cpu, memory, &state, &ram, rng.as_ref());
+    cpu.set_family_profiling(true);
+    pass(cpu, memory, keypad, ips, limit)?;
+    let mut families = *cpu.get_family_profile().unwrap();
+    cpu.set_family_profiling(false);
+    let overhead = timer_overhead();
+    for (count, time) in families.iter_mut() {
+        *time = time.saturating_sub(overhead * *count as u32);
+    }
+    return Ok(Report { ips, elapsed, cycles, frames, families });
+}
+
+// Puts the CPU, RAM and random source back the way they were, dropping the instructions and blocks
+// cached by the previous pass as well.
+fn restore(cpu: &mut CPU, memory: &mut Memory, state: &CpuState, ram: &[u8], rng: &dyn RandomSource) {
+    cpu.reset();
+    cpu.set_state(state).expect("the CPU's own state always fits it");
+    cpu.set_random_source(rng.clone_box());
+    memory.set_ram(ram);
+}
+
+// What timing a single instruction costs by itself, subtracted from the family times.
+fn timer_overhead() -> Duration {
+    const SAMPLES: u32 = 100_000;
+    let mut total = Duration::ZERO;
+    for _ in 0..SAMPLES {
+        let started = Instant::now();
+        total += started.elapsed();
+    }
+    return total / SAMPLES;
+}
+
+fn pass(cpu: &mut CPU, memory: &mut Memory, keypad: &mut Keypad, ips: u32, limit: Limit) -> Result<(Duration, u64, u64), Fault> {
+    let start = Instant::now();
+    let first_cycle = cpu.get_cycles();
+    let mut frames: u64 = 0;
+    loop {
+        let cycles = cpu.get_cycles() - first_cycle;
+        let budget = match limit {
+            Limit::Time(duration) if start.elapsed() >= duration => break,
+            Limit::Cycles(total) if cycles >= total => break,
+            Limit::Cycles(total) => (total - cycles).min(ips as u64) as u32,
+            Limit::Time(_) => ips,
+        };
+
+        // One frame, the same as the main loop.
+        cpu.run(memory, keypad, budget)?;
+        if cpu.get_delay_timer() > 0 {
+            cpu.dec_delay_timer();
+        }
+        cpu.tick_random_source();
+        cpu.vblank();
+        if cpu.get_sound_timer() > 0 {
+            cpu.dec_sound_timer();
+        }
+        frames += 1;
+    }
+    return Ok((start.elapsed(), cpu.get_cycles() - first_cycle, frames));
+}
+
+impl Report {
+    // Instructions executed per opcode family in the timed pass, with their time.
+    pub fn get_families(&self) -> &FamilyProfile {
+        return &self.families;
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let seconds = self.elapsed.as_secs_f64();
+        let frames_per_second = self.frames as f64 / seconds;
+        writeln!(f, "{} instructions, {} frames in {:.3}s ({} instructions per frame)", self.cycles, self.frames, seconds, self.ips)?;
+        writeln!(f, "{:.0} instructions/s", self.cycles as f64 / seconds)?;
+        writeln!(f, "{:.0} frames/s ({:.1}x real time)", frames_per_second, frames_per_second / FRAME_RATE)?;
+
+        let total: Duration = self.families.iter().map(|(_, time)| *time).sum();
+        let mut families = self.families.iter().enumerate().filter(|(_, (count, _))| *count > 0).collect::<Vec<_>>();
+        families.sort_by_key(|(_, (_, time))| std::cmp::Reverse(*time));
+        writeln!(f)?;
+        writeln!(f, "{:<10} {:>12} {:>7} {:>9}", "family", "instructions", "time", "ns/instr")?;
+        for (family, (count, time)) in families {
+            writeln!(f, "{:<10} {:>12} {:>6.1}% {:>9.1}",
+                FAMILIES[family], count, 100.0 * time.as_secs_f64() / total.as_secs_f64(), time.as_nanos() as f64 / *count as f64)?;
+        }
+        return Ok(());
+    }
+}
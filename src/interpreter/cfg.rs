@@ -0,0 +1,185 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+
+use super::analysis::Analysis;
+use super::analysis::Edge;
+use super::analysis::EdgeKind;
+use super::cpu::Instructions;
+use super::disassembler::disassemble;
+
+// Control-flow graph of the reachable code of a ROM, made of basic blocks: straight-line runs of
+// instructions that are only entered at the top and only left at the bottom. Skips end a block with
+// two edges, to the next instruction (the condition doesn't hold) and over it (it holds).
+
+#[derive(Serialize)]
+pub struct Instruction {
+    pub address: u16,
+    pub opcode: u16,
+    pub text: String,
+}
+
+#[derive(Serialize)]
+pub struct Block {
+    pub start: u16,
+    pub instructions: Vec<Instruction>,
+    // Entry point of the main program or of a subroutine.
+    pub entry: bool,
+}
+
+#[derive(Serialize)]
+pub struct BlockEdge {
+    // Start of the source block.
+    pub from: u16,
+    // Start of the target block, or the target address if it lies outside the ROM.
+    pub to: u16,
+    pub kind: EdgeKind,
+    // Condition under which a skip is taken, or not taken for the edge to the next instruction.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+    pub outside: bool,
+}
+
+#[derive(Serialize)]
+pub struct ControlFlowGraph {
+    pub load_address: u16,
+    pub blocks: Vec<Block>,
+    pub edges: Vec<BlockEdge>,
+}
+
+impl ControlFlowGraph {
+    pub fn new(rom: &[u8], load_address: u16) -> Self {
+        return ControlFlowGraph::from_analysis(&Analysis::new(rom, load_address));
+    }
+
+    pub fn from_analysis(analysis: &Analysis) -> Self {
+        let mut incoming: BTreeMap<u16, Vec<&Edge>> = BTreeMap::new();
+        for edge in analysis.edges.iter() {
+            incoming.entry(edge.to).or_default().push(edge);
+        }
+
+        // An instruction falls through into the next one of its block if that is its only successor.
+        let falls_through = |address: u16| -> bool {
+            let mut edges = analysis.edges_from(address);
+            let first = edges.next();
+            return edges.next().is_none() && first.is_some_and(|edge| edge.kind == EdgeKind::Next)
+                && !analysis.outside.iter().any(|edge| edge.from == address);
+        };
+        let entries: BTreeSet<u16> = analysis.subroutines.iter().copied().chain([analysis.load_address]).collect();
+        let leaders: BTreeSet<u16> = analysis.code.keys().copied()
+            .filter(|address| {
+                if entries.contains(address) {
+                    return true;
+                }
+                let edges = incoming.get(address).map(Vec::as_slice).unwrap_or(&[]);
+                return !matches!(edges, [edge] if edge.kind == EdgeKind::Next && falls_through(edge.from));
+            })
+            .collect();
+
+        let mut blocks = Vec::new();
+        let mut edges = Vec::new();
+        for start in leaders.iter().copied() {
+            let mut instructions = Vec::new();
+            let mut address = start;
+            loop {
+                let opcode = analysis.code[&address];
+                instructions.push(Instruction { address, opcode, text: disassemble(opcode) });
+                let next = address.wrapping_add(2);
+                if !falls_through(address) || leaders.contains(&next) {
+                    break;
+                }
+                address = next;
+            }
+
+            let opcode = analysis.code[&address];
+            for edge in analysis.edges_from(address) {
+                edges.push(BlockEdge { from: start, to: edge.to, kind: edge.kind, condition: condition(opcode, edge.kind), outside: false });
+            }
+            for edge in analysis.outside.iter().filter(|edge| edge.from == address) {
+                edges.push(BlockEdge { from: start, to: edge.to, kind: edge.kind, condition: condition(opcode, edge.kind), outside: true });
+            }
+            blocks.push(Block { start, instructions, entry: entries.contains(&start) });
+        }
+        return ControlFlowGraph { load_address: analysis.load_address, blocks, edges };
+    }
+
+    pub fn to_json(&self) -> String {
+        return serde_json::to_string_pretty(self).expect("Failed to serialize control-flow graph.");
+    }
+
+    // Blocks are boxes listing their disassembly, entry points have a double border. Jumps are solid,
+    // calls bold, skips dashed and labelled with their condition, edges leaving the ROM end in a dotted node.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n");
+        for block in self.blocks.iter() {
+            let mut label = String::new();
+            for instruction in block.instructions.iter() {
+                label += &format!("0x{:03X}  {}\\l", instruction.address, instruction.text);
+            }
+            let peripheries = if block.entry { 2 } else { 1 };
+            dot += &format!("    b{:03X} [label=\"{}\", peripheries={}];\n", block.start, label, peripheries);
+        }
+        let outside: BTreeSet<u16> = self.edges.iter().filter(|edge| edge.outside).map(|edge| edge.to).collect();
+        for address in outside {
+            dot += &format!("    x{:03X} [label=\"0x{:03X}\", style=dotted];\n", address, address);
+        }
+        for edge in self.edges.iter() {
+            let target = if edge.outside { 'x' } else { 'b' };
+            let style = match edge.kind {
+                EdgeKind::Next => "solid",
+                EdgeKind::Jump => "solid, color=blue",
+                EdgeKind::Call => "bold, color=darkgreen",
+                EdgeKind::Skip => "dashed",
+                EdgeKind::Table => "solid, color=purple",
+            };
+            let label = edge.condition.clone().unwrap_or_else(|| edge.kind.name().to_string());
+            dot += &format!("    b{:03X} -> {}{:03X} [style={}, label=\"{}\"];\n", edge.from, target, edge.to, style, label);
+        }
+        dot += "}\n";
+        return dot;
+    }
+}
+
+// The condition of a skip, for the edge over the next instruction (Skip) or to it (Next).
+fn condition(opcode: u16, kind: EdgeKind) -> Option<String> {
+    let x = (opcode & 0x0F00) >> 8;
+    let y = (opcode & 0x00F0) >> 4;
+    let nn = opcode & 0x00FF;
+    let (taken, not_taken) = match Instructions::decode(opcode)? {
+        Instructions::Instruction3xnn => (format!("V{:X} == 0x{:02X}", x, nn), format!("V{:X} != 0x{:02X}", x, nn)),
+        Instructions::Instruction4xnn => (format!("V{:X} != 0x{:02X}", x, nn), format!("V{:X} == 0x{:02X}", x, nn)),
+        Instructions::Instruction5xy0 => (format!("V{:X} == V{:X}", x, y), format!("V{:X} != V{:X}", x, y)),
+        Instructions::Instruction9xy0 => (format!("V{:X} != V{:X}", x, y), format!("V{:X} == V{:X}", x, y)),
+        Instructions::Instructionex9e => (format!("key V{:X} down", x), format!("key V{:X} up", x)),
+        Instructions::Instructionexa1 => (format!("key V{:X} up", x), format!("key V{:X} down", x)),
+        _ => return None,
+    };
+    return match kind {
+        EdgeKind::Skip => Some(taken),
+        EdgeKind::Next => Some(not_taken),
+        _ => None,
+    };
+}
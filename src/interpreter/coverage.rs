@@ -0,0 +1,253 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+use super::cpu::Instructions;
+use super::disassembler::disassemble;
+
+const EXECUTED: u8 = 1;
+const READ: u8 = 2;
+const WRITTEN: u8 = 4;
+
+// Data bytes listed per line of the disassembly.
+const DATA_LINE: usize = 8;
+
+// Records which bytes of RAM were executed as code, read as data (sprites, Fx65, the stack in RAM) or
+// written, and which way every conditional skip went, to check how much of a ROM a run exercises.
+pub struct Coverage {
+    flags: Vec<u8>,
+    // Last opcode executed at every address, for the disassembly.
+    opcodes: Vec<u16>,
+    // Times every skip instruction did and didn't skip.
+    branches: BTreeMap<u16, (u64, u64)>,
+    load_address: usize,
+    rom: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Summary {
+    executed: usize,
+    read: usize,
+    written: usize,
+    untouched: usize,
+    branches: usize,
+    // Skips that went both ways.
+    covered_branches: usize,
+}
+
+impl Coverage {
+    pub fn new(memory_size: usize, load_address: u16, rom: &[u8]) -> Self {
+        Coverage {
+            flags: vec![0; memory_size],
+            opcodes: vec![0; memory_size],
+            branches: BTreeMap::new(),
+            load_address: load_address as usize,
+            rom: rom.to_vec(),
+        }
+    }
+
+    // The ROM was patched while running (--watch patch): what ran so far stays recorded, the report lists
+    // the new bytes.
+    pub fn set_rom(&mut self, rom: &[u8]) {
+        self.rom = rom.to_vec();
+    }
+
+    // Called for every executed instruction, with its address and the address execution continues at.
+    pub fn execute(&mut self, pc: u16, opcode: u16, next: u16) {
+        let size = self.flags.len();
+        let address = pc as usize % size;
+        self.flags[address] |= EXECUTED;
+        self.flags[(address + 1) % size] |= EXECUTED;
+        self.opcodes[address] = opcode;
+        if is_skip(opcode) {
+            let branch = self.branches.entry(pc).or_default();
+            if next == pc.wrapping_add(4) {
+                branch.0 += 1;
+            } else {
+                branch.1 += 1;
+            }
+        }
+    }
+
+    // Addresses are the resolved ones, inside RAM.
+    pub fn read(&mut self, address: usize) {
+        let size = self.flags.len();
+        self.flags[address % size] |= READ;
+    }
+
+    pub fn write(&mut self, address: usize) {
+        let size = self.flags.len();
+        self.flags[address % size] |= WRITTEN;
+    }
+
+    fn rom_range(&self) -> std::ops::Range<usize> {
+        return self.load_address..self.load_address + self.rom.len();
+    }
+
+    fn summary(&self) -> Summary {
+        let mut summary = Summary::default();
+        for address in self.rom_range() {
+            let flags = self.flags[address];
+            summary.executed += (flags & EXECUTED != 0) as usize;
+            summary.read += (flags & READ != 0) as usize;
+            summary.written += (flags & WRITTEN != 0) as usize;
+            summary.untouched += (flags == 0) as usize;
+        }
+        summary.branches = self.branches.len();
+        summary.covered_branches = self.branches.values().filter(|(taken, not_taken)| *taken > 0 && *not_taken > 0).count();
+        return summary;
+    }
+
+    // Lines of the annotated disassembly of the ROM: flags ("xrw"), address, bytes, text and whether it's fully covered.
+    fn listing(&self) -> Vec<(String, usize, String, String, Class)> {
+        let mut lines = Vec::new();
+        let mut address = self.load_address;
+        let end = self.rom_range().end;
+        while address < end {
+            let flags = self.flags[address];
+            if flags & EXECUTED != 0 && address + 1 < end {
+                let opcode = self.opcodes[address];
+                let (text, class) = match self.branches.get(&(address as u16)) {
+                    Some((taken, not_taken)) => {
+                        let class = if *taken > 0 && *not_taken > 0 { Class::Executed } else { Class::Partial };
+                        (format!("{:<16} ; skipped {}, not skipped {}", disassemble(opcode), taken, not_taken), class)
+                    },
+                    None => (disassemble(opcode), Class::Executed),
+                };
+                lines.push((flag_text(flags), address, format!("{:04X}", opcode), text, class));
+                address += 2;
+                continue;
+            }
+            // Code that never ran, as far as it can be told apart from data.
+            let offset = address - self.load_address;
+            if flags == 0 && offset.is_multiple_of(2) && address + 1 < end && self.flags[address + 1] == 0 {
+                let opcode = u16::from_be_bytes([self.rom[offset], self.rom[offset + 1]]);
+                if Instructions::decode(opcode).is_some() {
+                    lines.push((flag_text(flags), address, format!("{:04X}", opcode), disassemble(opcode), Class::Untouched));
+                    address += 2;
+                    continue;
+                }
+            }
+            // Bytes that weren't executed, grouped while their flags stay the same.
+            let mut length = 1;
+            while length < DATA_LINE && address + length < end && self.flags[address + length] == flags
+                && self.flags[address + length] & EXECUTED == 0 {
+                length += 1;
+            }
+            let bytes = &self.rom[address - self.load_address..address - self.load_address + length];
+            let hex = bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<_>>().join(" ");
+            let class = if flags & WRITTEN != 0 {
+                Class::Written
+            } else if flags & READ != 0 {
+                Class::Read
+            } else if flags == 0 {
+                Class::Untouched
+            } else {
+                Class::Executed
+            };
+            let text = bytes.iter().map(|byte| format!("0x{:02X}", byte)).collect::<Vec<_>>().join(", ");
+            lines.push((flag_text(flags), address, hex, format!("db {}", text), class));
+            address += length;
+        }
+        return lines;
+    }
+
+    pub fn report(&self) -> String {
+        let summary = self.summary();
+        let percent = |count: usize| 100.0 * count as f64 / self.rom.len().max(1) as f64;
+        let range = self.rom_range();
+        let mut report = format!("Coverage of the ROM at 0x{:03X}-0x{:03X} ({} bytes)\n", range.start, range.end.saturating_sub(1), self.rom.len());
+        report += &format!("  executed   {:>6} bytes {:>6.1}%\n", summary.executed, percent(summary.executed));
+        report += &format!("  read       {:>6} bytes {:>6.1}%\n", summary.read, percent(summary.read));
+        report += &format!("  written    {:>6} bytes {:>6.1}%\n", summary.written, percent(summary.written));
+        report += &format!("  untouched  {:>6} bytes {:>6.1}%\n", summary.untouched, percent(summary.untouched));
+        report += &format!("  branches   {} of {} skips went both ways\n", summary.covered_branches, summary.branches);
+        report += "\nx executed, r read, w written, ! skip that only went one way\n\n";
+        for (flags, address, bytes, text, class) in self.listing() {
+            let marker = if class == Class::Partial { "!" } else { " " };
+            report += &format!("{}{} 0x{:03X}  {:<23} {}\n", marker, flags, address, bytes, text);
+        }
+        return report;
+    }
+
+    pub fn html_report(&self) -> String {
+        let summary = self.summary();
+        let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>ROM coverage</title>\n<style>\n");
+        html += "body { font-family: monospace; background: #1a1c2c; color: #f4f4f4; }\n";
+        html += ".executed { color: #a7f070; } .partial { color: #ef7d57; } .read { color: #73eff7; }\n";
+        html += ".written { color: #ffcd75; } .untouched { color: #566c86; }\n</style>\n</head>\n<body>\n";
+        html += &format!("<h1>Coverage of the ROM at 0x{:03X} ({} bytes)</h1>\n<ul>\n", self.load_address, self.rom.len());
+        html += &format!("<li class=\"executed\">{} bytes executed</li>\n", summary.executed);
+        html += &format!("<li class=\"read\">{} bytes read</li>\n", summary.read);
+        html += &format!("<li class=\"written\">{} bytes written</li>\n", summary.written);
+        html += &format!("<li class=\"untouched\">{} bytes untouched</li>\n", summary.untouched);
+        html += &format!("<li class=\"partial\">{} of {} skips went both ways</li>\n</ul>\n<pre>\n", summary.covered_branches, summary.branches);
+        for (flags, address, bytes, text, class) in self.listing() {
+            html += &format!("<span class=\"{}\">{} 0x{:03X}  {:<23} {}</span>\n", class.name(), flags, address, bytes, text);
+        }
+        html += "</pre>\n</body>\n</html>\n";
+        return html;
+    }
+
+    // HTML if the path ends in .html, text otherwise.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let report = if path.ends_with(".html") { self.html_report() } else { self.report() };
+        return fs::write(path, report);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Class {
+    Executed,
+    // A skip that only went one way.
+    Partial,
+    Read,
+    Written,
+    Untouched,
+}
+
+impl Class {
+    fn name(&self) -> &str {
+        match self {
+            Class::Executed => "executed",
+            Class::Partial => "partial",
+            Class::Read => "read",
+            Class::Written => "written",
+            Class::Untouched => "untouched",
+        }
+    }
+}
+
+fn flag_text(flags: u8) -> String {
+    return [(EXECUTED, 'x'), (READ, 'r'), (WRITTEN, 'w')].iter()
+        .map(|(flag, letter)| if flags & flag != 0 { *letter } else { '-' })
+        .collect();
+}
+
+// 3xnn, 4xnn, 5xy0, 9xy0, Ex9E and ExA1.
+fn is_skip(opcode: u16) -> bool {
+    return matches!(opcode & 0xF000, 0x3000 | 0x4000 | 0x5000 | 0x9000)
+        || opcode & 0xF0FF == 0xE09E || opcode & 0xF0FF == 0xE0A1;
+}
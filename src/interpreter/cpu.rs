@@ -20,14 +20,26 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use rand::Rng;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::rc::Rc;
+use std::time::Duration;
+use std::time::Instant;
 
-use super::display::Display;
+use super::bench::FamilyProfile;
+use super::coverage::Coverage;
 use super::memory::Memory;
+use super::profiler::Profiler;
+use super::memory::BoundsPolicy;
+use super::memory::MemoryMap;
 use super::keypad::Keypad;
+use super::quirks::Quirks;
+use super::random::DefaultRandom;
+use super::random::RandomSource;
 
 
-enum Instructions {
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Instructions {
     Instruction00e0 = 0x1,  // Clear Display
     Instruction1nnn = 0x2,  // Jump
     Instruction2nnn = 0x3,  // Call Subroutine
@@ -62,46 +74,373 @@ enum Instructions {
     Instructionfx18 = 0x32, // Timer (Sound) Set
     Instructionex9e = 0x33, // Skip if key
     Instructionexa1 = 0x34, // Skip if key
+    Instructionfx30 = 0x35, // Big font character
 }
 
-const WIDTH: usize = 64;
-const HEIGHT: usize = 32;
+impl Instructions {
+    // None for opcodes that aren't part of the instruction set, e.g. data following the code.
+    pub fn decode(opcode: u16) -> Option<Self> {
+        match opcode & 0xf000 {
+            0x0000 => {
+                match opcode & 0x00ff {
+                    0x00E0 => {
+                        Some(Instructions::Instruction00e0)
+                    },
+                    0x00EE => {
+                        Some(Instructions::Instruction00ee)
+                    },
+                    _ => {
+                        return None;
+                    }
+                }
+            },
+            0x1000 => {
+                Some(Instructions::Instruction1nnn)
+            },
+            0x2000 => {
+                Some(Instructions::Instruction2nnn)
+            },
+            0x3000 => {
+                Some(Instructions::Instruction3xnn)
+            },
+            0x4000 => {
+                Some(Instructions::Instruction4xnn)
+            },
+            0x5000 => {
+                Some(Instructions::Instruction5xy0)
+            },
+            0x6000 => {
+                Some(Instructions::Instruction6xnn)
+            },
+            0x7000 => {
+                Some(Instructions::Instruction7xnn)
+            },
+            0x8000 => {
+                match opcode & 0x000f {
+                    0x0000 => {
+                        Some(Instructions::Instruction8xy0)
+                    },
+                    0x0001 => {
+                        Some(Instructions::Instruction8xy1)
+                    },
+                    0x0002 => {
+                        Some(Instructions::Instruction8xy2)
+                    },
+                    0x0003 => {
+                        Some(Instructions::Instruction8xy3)
+                    },
+                    0x0004 => {
+                        Some(Instructions::Instruction8xy4)
+                    },
+                    0x0005 => {
+                        Some(Instructions::Instruction8xy5)
+                    },
+                    0x006 => {
+                        Some(Instructions::Instruction8xy6)
+                    },
+                    0x0007 => {
+                        Some(Instructions::Instruction8xy7)
+                    },
+                    0x00e => {
+                        Some(Instructions::Instruction8xye)
+                    }
+                    _ => {
+                        return None;
+                    }
+                }
+            },
+            0x9000 => {
+                Some(Instructions::Instruction9xy0)
+            },
+            0xA000 => {
+                Some(Instructions::Instructionannn)
+            },
+            0xB000 => {
+                Some(Instructions::Instructionbnnn)
+            },
+            0xC000 => {
+                Some(Instructions::Instructioncxnn)
+            }
+            0xD000 => {
+                Some(Instructions::Instructiondxyn)
+            },
+            0xE000 => {
+                match opcode & 0x00ff {
+                    0x009E => {
+                        Some(Instructions::Instructionex9e)
+                    },
+                    0x00A1 => {
+                        Some(Instructions::Instructionexa1)
+                    },
+                    _ => {
+                        return None;
+                    }
+                }
+            },
+            0xF000 => {
+                match opcode & 0x00ff {
+                    0x0033 => {
+                        Some(Instructions::Instructionfx33)
+                    },
+                    0x0029 => {
+                        Some(Instructions::Instructionfx29)
+                    },
+                    0x0030 => {
+                        Some(Instructions::Instructionfx30)
+                    },
+                    0x0055 => {
+                        Some(Instructions::Instructionfx55)
+                    },
+                    0x0065 => {
+                        Some(Instructions::Instructionfx65)
+                    },
+                    0x001E => {
+                        Some(Instructions::Instructionfx1e)
+                    },
+                    0x000A => {
+                        Some(Instructions::Instructionfx0a)
+                    },
+                    0x0007 => {
+                        Some(Instructions::Instructionfx07)
+                    },
+                    0x0015 => {
+                        Some(Instructions::Instructionfx15)
+                    },
+                    0x0018 => {
+                        Some(Instructions::Instructionfx18)
+                    },
+                    _ => {
+                        return None;
+                    }
+                }
+            }
+            _ => {
+                return None;
+            }
+        }
+    }
+}
+
+pub const WIDTH: usize = 64;
+pub const HEIGHT: usize = 32;
+
+// An instruction with its operands extracted, cached by address.
+#[derive(Clone, Copy)]
+struct Decoded {
+    instruction: Instructions,
+    opcode: u16,
+    x: u16,
+    y: u16,
+}
+
+// Instructions that can change the flow of control end a block.
+fn ends_block(instruction: Instructions) -> bool {
+    return matches!(instruction,
+        Instructions::Instruction00ee | Instructions::Instruction1nnn | Instructions::Instruction2nnn |
+        Instructions::Instructionbnnn | Instructions::Instruction3xnn | Instructions::Instruction4xnn |
+        Instructions::Instruction5xy0 | Instructions::Instruction9xy0 | Instructions::Instructionex9e |
+        Instructions::Instructionexa1 | Instructions::Instructionfx0a);
+}
+
+const MAX_BLOCK_LENGTH: usize = 32;
+
+// Straight-line code, pre-decoded so it runs without fetching or decoding (a threaded-code backend).
+struct Block {
+    // Address after the last instruction, writes below it (down to the start) invalidate the block.
+    end: usize,
+    instructions: Vec<Decoded>,
+}
+
+// How instructions are executed, both give identical results.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Backend {
+    // One instruction at a time, through the decoded instruction cache.
+    Interpreter,
+    // Whole blocks of pre-decoded instructions at a time.
+    Blocks,
+}
+
+pub const BACKENDS: [&str; 2] = ["interpreter", "blocks"];
+
+impl Backend {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "interpreter" => Some(Backend::Interpreter),
+            "blocks" => Some(Backend::Blocks),
+            _ => None,
+        }
+    }
+}
+
+pub const DEFAULT_STACK_DEPTH: usize = 16;
+
+// Errors that stop the CPU, it stays on the faulting instruction.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Fault {
+    StackOverflow { pc: u16, depth: usize },
+    StackUnderflow { pc: u16 },
+    MemoryRead { pc: u16, address: usize },
+    MemoryWrite { pc: u16, address: usize },
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::StackOverflow { pc, depth } => write!(f, "stack overflow at {:03X} ({} nested calls)", pc, depth),
+            Fault::StackUnderflow { pc } => write!(f, "stack underflow at {:03X} (return without a call)", pc),
+            Fault::MemoryRead { pc, address } => write!(f, "read from {:03X} past the end of memory at {:03X}", address, pc),
+            Fault::MemoryWrite { pc, address } => write!(f, "write to {:03X} past the end of memory at {:03X}", address, pc),
+        }
+    }
+}
+
+// Snapshot of everything the CPU holds, used for save states.
+#[derive(Clone, PartialEq)]
+pub struct CpuState {
+    pub buffer: [[bool; WIDTH]; HEIGHT],
+    pub pc: u16,
+    pub i: u16,
+    // Number of return addresses on the stack, or the stack's address when it lives in RAM.
+    pub sp: u16,
+    // Empty when the stack lives in RAM.
+    pub stack: Vec<u16>,
+    pub v: [u8; 16],
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
 
 pub struct CPU {
     buffer: [[bool; WIDTH]; HEIGHT],
     pc: u16,
     i: u16,
     sp: u16,
-    stack: [u16; 16],
+    stack: Vec<u16>,
     v: [u8; 16],
     delay_timer: u8,
     sound_timer: u8,
+    rng: Box<dyn RandomSource>,
+    quirks: Quirks,
+    trace: bool,
+    load_address: u16,
+    font_address: u16,
+    big_font_address: u16,
+    // None is unlimited.
+    stack_depth: Option<usize>,
+    // The VIP kept return addresses in RAM, growing down from the map's stack address.
+    stack_in_memory: bool,
+    stack_address: u16,
+    // I wraps around at the end of RAM with the wrap bounds policy.
+    wrap_index: bool,
+    memory_size: usize,
+    // Instructions that accessed memory past the end of RAM, each one is reported once.
+    wrapped: BTreeSet<u16>,
+    // Set by the 60Hz tick if Dxyn was waiting for it (display wait quirk).
+    vblank: bool,
+    waiting_for_vblank: bool,
+    // Decoded instructions by address, entries are dropped when Memory reports writes to them.
+    cache: Vec<Option<Decoded>>,
+    cache_enabled: bool,
+    cache_generation: u64,
+    backend: Backend,
+    // Compiled blocks by start address, and how many blocks cover each byte of RAM.
+    blocks: Vec<Option<Rc<Block>>>,
+    block_coverage: Vec<u16>,
+    block_generation: u64,
+    // Instructions executed so far.
+    cycles: u64,
+    // Only while benchmarking, see bench.rs.
+    family_profile: Option<FamilyProfile>,
+    profiler: Option<Profiler>,
+    coverage: Option<Coverage>,
 }
 
 impl CPU {
-    pub fn new() -> Self {
+    // Execution starts at the memory map's load address.
+    pub fn new(map: &MemoryMap) -> Self {
         Self {
             buffer: [[false; WIDTH]; HEIGHT],
-            pc: 0x200,
+            pc: map.load_address,
             i: 0x0,
             sp: 0x0,
-            stack: [0; 16],
+            stack: Vec::new(),
             v: [0; 16],
             delay_timer: 0x0,
-            sound_timer: 0x0
+            sound_timer: 0x0,
+            rng: Box::new(DefaultRandom::new(0)),
+            quirks: Quirks::new(),
+            trace: false,
+            load_address: map.load_address,
+            font_address: map.font_address,
+            big_font_address: map.big_font_address(),
+            stack_depth: Some(DEFAULT_STACK_DEPTH),
+            stack_in_memory: false,
+            stack_address: map.stack_address,
+            wrap_index: map.bounds == BoundsPolicy::Wrap,
+            memory_size: map.size,
+            wrapped: BTreeSet::new(),
+            vblank: false,
+            waiting_for_vblank: false,
+            cache: vec![None; map.size],
+            cache_enabled: true,
+            cache_generation: u64::MAX,
+            backend: Backend::Interpreter,
+            blocks: vec![None; map.size],
+            block_coverage: vec![0; map.size],
+            block_generation: u64::MAX,
+            cycles: 0,
+            family_profile: None,
+            profiler: None,
+            coverage: None,
         }
         
     }
 
+    // Executes up to `cycles` instructions with the selected backend, stopping early when Dxyn waits for the next frame.
+    pub fn run(&mut self, memory: &mut Memory, keypad: &mut Keypad, cycles: u32) -> Result<(), Fault> {
+        match self.backend {
+            Backend::Interpreter => {
+                for _ in 0..cycles {
+                    self.execute(memory, keypad)?;
+                    if self.waiting_for_vblank {
+                        break;
+                    }
+                }
+            },
+            Backend::Blocks => {
+                let mut remaining = cycles;
+                while remaining > 0 && !self.waiting_for_vblank {
+                    remaining -= self.run_block(memory, keypad, remaining)?;
+                }
+            },
+        }
+        return Ok(());
+    }
+
     // Let's only expose "execute" publicly, we'll handle fetching and decoding privately.
-    pub fn execute(&mut self, display: &mut Display, memory: &mut Memory, keypad:&mut Keypad) {
-        let opcode = self.fetch(memory);
-        let instruction = self.decode(opcode);
+    pub fn execute(&mut self, memory: &mut Memory, keypad:&mut Keypad) -> Result<(), Fault> {
+        let decoded = self.fetch_decoded(memory)?;
+        return self.step(decoded, memory, keypad);
+    }
+
+    fn step(&mut self, decoded: Decoded, memory: &mut Memory, keypad: &mut Keypad) -> Result<(), Fault> {
+        let Decoded { instruction, opcode, x, y } = decoded;
 
-        // XY
-        let x = (opcode & 0x0f00) >> 8;
-        let y = (opcode & 0x00f0) >> 4;
+        // With the display wait quirk every Dxyn stays on the instruction until the next frame, then draws.
+        if self.quirks.display_wait && matches!(instruction, Instructions::Instructiondxyn) {
+            if !self.vblank {
+                self.waiting_for_vblank = true;
+                return Ok(());
+            }
+            self.vblank = false;
+        }
 
+        if self.trace {
+            eprintln!("{:04X}: {:04X}  I={:03X} SP={:X} DT={:02X} ST={:02X} V={:02X?}",
+                self.pc, opcode, self.i, self.sp, self.delay_timer, self.sound_timer, self.v);
+        }
+
+        let pc = self.pc;
+        let started = self.family_profile.is_some().then(Instant::now);
         match instruction {
             Instructions::Instruction00e0 => {
                 for y in 0..32 {
@@ -117,8 +456,8 @@ impl CPU {
             },
             Instructions::Instruction2nnn => {
                 let nnn: u16 = opcode & 0x0fff;
-                self.stack[self.sp as usize] = self.pc;
-                self.sp += 1;
+                // The return address is the instruction after the call.
+                self.push(memory, self.pc + 2)?;
                 self.pc = nnn;
             },
             Instructions::Instruction6xnn => {
@@ -133,34 +472,42 @@ impl CPU {
             },
             Instructions::Instructiondxyn => {
                 let n = (opcode & 0x000f) as usize;
-                let init_x = self.v[x as usize] as usize;
-                let init_y = self.v[y as usize] as usize;
-                
+                // The start coordinate always wraps, the rest of the sprite is clipped or wraps depending on the quirk.
+                // SUPER-CHIP's hi-res mode, where VF counts the rows that collided or were clipped, is out of scope (see README).
+                let init_x = self.v[x as usize] as usize % WIDTH;
+                let init_y = self.v[y as usize] as usize % HEIGHT;
+
                 self.v[0xF] = 0;
                 for i in 0..n {
-                    let pixel: u16 = memory.get_from_index(self.i as usize + i as usize) as u16;
+                    let y = init_y + i;
+                    if y >= HEIGHT && self.quirks.clip {
+                        break;
+                    }
+                    let pixel: u16 = self.read_data(memory, self.i as usize + i)? as u16;
                     for j in 0..8 {
-                        let x = (init_x + j) % WIDTH;
-                        let y = (init_y + i) % HEIGHT;
+                        let x = init_x + j;
+                        if x >= WIDTH && self.quirks.clip {
+                            break;
+                        }
+                        let (x, y) = (x % WIDTH, y % HEIGHT);
                         if pixel & 0x80 >> j != 0 {
-                            self.v[0xF] |= self.buffer[y % HEIGHT][x % WIDTH] as u8;
+                            self.v[0xF] |= self.buffer[y][x] as u8;
                             self.buffer[y][x] ^= true;
                         }
                     }
                 }
-                display.draw(&self.buffer);
                 self.pc += 2;
             },
             Instructions::Instruction7xnn => {
                 let nn = opcode & 0x00ff;
-                self.v[x as usize] = self.v[x as usize].wrapping_add(nn as u8) & 0xFF;
+                self.v[x as usize] = self.v[x as usize].wrapping_add(nn as u8);
                 self.pc += 2;
             },
             Instructions::Instructionfx33 => {
                 let v = self.v[x as usize];
-                memory.set_from_index(self.i as usize, v / 100);
-                memory.set_from_index(self.i as usize + 1, (v % 100) / 10);
-                memory.set_from_index(self.i as usize  + 2, v % 10);
+                self.write(memory, self.i as usize, v / 100)?;
+                self.write(memory, self.i as usize + 1, (v % 100) / 10)?;
+                self.write(memory, self.i as usize + 2, v % 10)?;
                 self.pc += 2;
             },
             Instructions::Instruction3xnn => {
@@ -178,20 +525,21 @@ impl CPU {
                 if self.v[x as usize] != self.v[y as usize] { self.pc += 4; } else { self.pc += 2; }
             },
             Instructions::Instruction00ee => {
-                self.sp -= 1;
-                self.pc = self.stack[self.sp as usize];
-                self.pc += 2;
+                self.pc = self.pop(memory)?;
             },
             Instructions::Instruction8xy2 => {
                 self.v[x as usize] &= self.v[y as usize];
+                if self.quirks.vf_reset { self.v[0xF] = 0; }
                 self.pc += 2;
             },
             Instructions::Instruction8xy1 => {
                 self.v[x as usize] |= self.v[y as usize];
+                if self.quirks.vf_reset { self.v[0xF] = 0; }
                 self.pc += 2;
             },
             Instructions::Instruction8xy3 => {
                 self.v[x as usize] ^= self.v[y as usize];
+                if self.quirks.vf_reset { self.v[0xF] = 0; }
                 self.pc += 2;
             },
             Instructions::Instruction8xy4 => {
@@ -217,49 +565,55 @@ impl CPU {
                 self.pc += 2;
             },
             Instructions::Instruction8xy6 => {
-                let flag = self.v[y as usize] & 0b00000001 > 0;
-                self.v[x as usize] = self.v[y as usize] >> 1;
+                let source = if self.quirks.shift { x } else { y };
+                let flag = self.v[source as usize] & 0b00000001 > 0;
+                self.v[x as usize] = self.v[source as usize] >> 1;
                 self.v[0xF] = flag as u8;
                 self.pc += 2;
             },
             Instructions::Instruction8xye => {
-                let flag = self.v[y as usize] & 0b10000000 > 0;
-                self.v[x as usize] = self.v[y as usize] << 1;
+                let source = if self.quirks.shift { x } else { y };
+                let flag = self.v[source as usize] & 0b10000000 > 0;
+                self.v[x as usize] = self.v[source as usize] << 1;
                 self.v[0xF] = flag as u8;
                 self.pc += 2;
             },
             Instructions::Instructionfx55 => {
                 for i in 0..=x {
-                    memory.set_from_index(self.i as usize + i as usize, self.v[i as usize]);
+                    self.write(memory, self.i as usize + i as usize, self.v[i as usize])?;
                 }
-                self.i += x + 1; // do not use this unless you strictly use CHIP-8
+                if self.quirks.memory { self.add_to_index(x + 1); }
                 self.pc += 2;
             },
             Instructions::Instructionfx65 => {
                 for i in 0..=x {
-                    self.v[i as usize] = memory.get_from_index(self.i as usize + i as usize);
+                    self.v[i as usize] = self.read_data(memory, self.i as usize + i as usize)?;
                 }
-                self.i += x + 1; // do not use this unless you strictly use CHIP-8
+                if self.quirks.memory { self.add_to_index(x + 1); }
                 self.pc += 2;
             },
             Instructions::Instructionfx29 => {
-                self.i = (self.v[x as usize] * 5) as u16;
+                // Only the low nibble selects the glyph.
+                self.i = self.font_address + (self.v[x as usize] & 0xF) as u16 * 5;
+                self.pc += 2;
+            },
+            Instructions::Instructionfx30 => {
+                self.i = self.big_font_address + (self.v[x as usize] & 0xF) as u16 * 10;
                 self.pc += 2;
             },
             Instructions::Instructioncxnn => {
                 let nn = opcode & 0x00ff;
-                // I suppose this will suffice, honestly.
-                self.v[x as usize] = (rand::thread_rng().gen::<u16>() & nn) as u8;
+                self.v[x as usize] = self.rng.next(memory) & nn as u8;
                 self.pc += 2;
             },
             Instructions::Instructionbnnn => {
                 let nnn = opcode & 0x0fff;
-                self.pc = nnn + self.v[x as usize] as u16; // v[0] if you want XO-CHIP / SUPER-CHIP
-                self.pc += 2;
+                let register = if self.quirks.jump { x } else { 0 };
+                self.pc = nnn + self.v[register as usize] as u16;
             },
             Instructions::Instructionfx1e => {
-                self.i += self.v[x as usize] as u16;
-                self.v[0xF] = if self.i > 0x0F00 { 1 } else { 0 };
+                // VF is left alone, only the Amiga interpreter flagged overflows past 0xFFF.
+                self.add_to_index(self.v[x as usize] as u16);
                 self.pc += 2;
             },
             Instructions::Instructionfx07 => {
@@ -288,9 +642,8 @@ impl CPU {
                     self.pc += 2;
                     // resume execution
                     // self.flags &= 0xA;
-                } else {
-                    self.pc -= 2;
                 }
+                // otherwise stay on this instruction until a key is pressed
             },
             Instructions::Instructionexa1 => {
                 let pressed = keypad.is_key_down_emulator(self.v[x as usize]);
@@ -303,150 +656,362 @@ impl CPU {
                 self.pc += 2;
             }
         }
+        self.cycles += 1;
+        if let (Some(profile), Some(started)) = (self.family_profile.as_mut(), started) {
+            let family = &mut profile[(opcode >> 12) as usize];
+            family.0 += 1;
+            family.1 += started.elapsed();
+        }
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record(pc, opcode);
+        }
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.execute(pc, opcode, self.pc);
+        }
+        return Ok(());
     }
 
-    fn fetch(&self, memory: &mut Memory) -> u16 {
-        let part1 = memory.get_from_index(self.pc as usize) as u16;
-        let part2 = memory.get_from_index((self.pc + 1) as usize) as u16;
-        return part1 << 8 | part2;
+    fn stack_size(&self) -> usize {
+        if self.stack_in_memory {
+            return (self.stack_address - self.sp) as usize / 2;
+        }
+        return self.stack.len();
     }
 
-    fn decode(&self, opcode: u16) -> Instructions {
-        match opcode & 0xf000 {
-            0x0000 => {
-                match opcode & 0x00ff {
-                    0x00E0 => {
-                        Instructions::Instruction00e0
-                    },
-                    0x00EE => {
-                        Instructions::Instruction00ee
-                    },
-                    _ => {
-                        panic!("Unknown Instruction (0x0000 family), opcode: 0x{:X}", opcode);
-                    }
-                }
-            },
-            0x1000 => {
-                Instructions::Instruction1nnn
-            },
-            0x2000 => {
-                Instructions::Instruction2nnn
-            },
-            0x3000 => {
-                Instructions::Instruction3xnn
-            },
-            0x4000 => {
-                Instructions::Instruction4xnn
-            },
-            0x5000 => {
-                Instructions::Instruction5xy0
-            },
-            0x6000 => {
-                Instructions::Instruction6xnn
-            },
-            0x7000 => {
-                Instructions::Instruction7xnn
+    fn push(&mut self, memory: &mut Memory, address: u16) -> Result<(), Fault> {
+        let depth = self.stack_size();
+        if self.stack_depth.is_some_and(|limit| depth >= limit) || (self.stack_in_memory && self.sp < 2) {
+            return Err(Fault::StackOverflow { pc: self.pc, depth });
+        }
+        if self.stack_in_memory {
+            // Big endian, like the VIP.
+            self.sp -= 2;
+            self.write(memory, self.sp as usize, (address >> 8) as u8)?;
+            self.write(memory, self.sp as usize + 1, address as u8)?;
+        } else {
+            self.stack.push(address);
+            self.sp += 1;
+        }
+        return Ok(());
+    }
+
+    fn pop(&mut self, memory: &mut Memory) -> Result<u16, Fault> {
+        if self.stack_size() == 0 {
+            return Err(Fault::StackUnderflow { pc: self.pc });
+        }
+        if self.stack_in_memory {
+            let address = (self.read_data(memory, self.sp as usize)? as u16) << 8 | self.read_data(memory, self.sp as usize + 1)? as u16;
+            self.sp += 2;
+            return Ok(address);
+        }
+        self.sp -= 1;
+        return Ok(self.stack.pop().unwrap());
+    }
+
+    fn read(&self, memory: &mut Memory, address: usize) -> Result<u8, Fault> {
+        return memory.get_from_index(address).ok_or(Fault::MemoryRead { pc: self.pc, address });
+    }
+
+    // Reads as data rather than code, for the coverage report.
+    fn read_data(&mut self, memory: &mut Memory, address: usize) -> Result<u8, Fault> {
+        let value = self.read(memory, address)?;
+        self.report_wrap(address);
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.read(address);
+        }
+        return Ok(value);
+    }
+
+    fn write(&mut self, memory: &mut Memory, address: usize, value: u8) -> Result<(), Fault> {
+        memory.set_from_index(address, value).ok_or(Fault::MemoryWrite { pc: self.pc, address })?;
+        self.report_wrap(address);
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.write(address);
+        }
+        return Ok(());
+    }
+
+    fn add_to_index(&mut self, value: u16) {
+        self.i = self.i.wrapping_add(value);
+        if self.wrap_index {
+            // RAM sizes are powers of two.
+            self.i &= (self.memory_size - 1) as u16;
+        }
+    }
+
+    // Runs the block starting at pc, compiling it first if needed, and returns the number of instructions executed.
+    // The block is left as soon as the flow of control changes or it was overwritten.
+    fn run_block(&mut self, memory: &mut Memory, keypad: &mut Keypad, budget: u32) -> Result<u32, Fault> {
+        self.sync_blocks(memory);
+        let start = self.pc as usize;
+        let block = match self.blocks.get(start).and_then(|block| block.clone()) {
+            Some(block) => block,
+            None => match self.compile_block(memory, start) {
+                Some(block) => block,
+                None => {
+                    // Not decodable (or past the end of RAM), interpret it to get the same panic or fault.
+                    let opcode = self.fetch(memory, start)?;
+                    let decoded = self.decode_operands(opcode);
+                    self.step(decoded, memory, keypad)?;
+                    return Ok(1);
+                },
             },
-            0x8000 => {
-                match opcode & 0x000f {
-                    0x0000 => {
-                        Instructions::Instruction8xy0
-                    },
-                    0x0001 => {
-                        Instructions::Instruction8xy1
-                    },
-                    0x0002 => {
-                        Instructions::Instruction8xy2
-                    },
-                    0x0003 => {
-                        Instructions::Instruction8xy3
-                    },
-                    0x0004 => {
-                        Instructions::Instruction8xy4
-                    },
-                    0x0005 => {
-                        Instructions::Instruction8xy5
-                    },
-                    0x006 => {
-                        Instructions::Instruction8xy6
-                    },
-                    0x0007 => {
-                        Instructions::Instruction8xy7
-                    },
-                    0x00e => {
-                        Instructions::Instruction8xye
-                    }
-                    _ => {
-                        panic!("Unknown Instruction (0x8000 family), opcode: 0x{:X}", opcode);
-                    }
+        };
+
+        let mut executed = 0;
+        for (index, decoded) in block.instructions.iter().enumerate() {
+            if executed == budget {
+                break;
+            }
+            self.step(*decoded, memory, keypad)?;
+            executed += 1;
+            if self.pc as usize != start + 2 * (index + 1) {
+                break;
+            }
+            if memory.has_writes() || memory.get_generation() != self.block_generation {
+                self.sync_blocks(memory);
+                if self.blocks[start].is_none() {
+                    break;
                 }
-            },
-            0x9000 => {
-                Instructions::Instruction9xy0
-            },
-            0xA000 => {
-                Instructions::Instructionannn
-            },
-            0xB000 => {
-                Instructions::Instructionbnnn
-            },
-            0xC000 => {
-                Instructions::Instructioncxnn
             }
-            0xD000 => {
-                Instructions::Instructiondxyn
-            },
-            0xE000 => {
-                match opcode & 0x00ff {
-                    0x009E => {
-                        Instructions::Instructionex9e
-                    },
-                    0x00A1 => {
-                        Instructions::Instructionexa1
-                    },
-                    _ => {
-                        panic!("Unknown Instruction (0xE000 family), opcode: 0x{:X}", opcode);
+        }
+        return Ok(executed);
+    }
+
+    fn compile_block(&mut self, memory: &mut Memory, start: usize) -> Option<Rc<Block>> {
+        let mut instructions = Vec::<Decoded>::new();
+        let mut address = start;
+        while instructions.len() < MAX_BLOCK_LENGTH && address + 1 < self.memory_size {
+            let Some(opcode) = self.fetch(memory, address).ok() else { break };
+            let Some(instruction) = Instructions::decode(opcode) else { break };
+            instructions.push(Decoded {
+                instruction,
+                opcode,
+                x: (opcode & 0x0f00) >> 8,
+                y: (opcode & 0x00f0) >> 4,
+            });
+            address += 2;
+            if ends_block(instruction) {
+                break;
+            }
+        }
+        if instructions.is_empty() {
+            return None;
+        }
+
+        for byte in start..address {
+            self.block_coverage[byte] += 1;
+        }
+        let block = Rc::new(Block { end: address, instructions });
+        self.blocks[start] = Some(block.clone());
+        return Some(block);
+    }
+
+    // Drops the blocks covering addresses written since the last call, or all of them if RAM was replaced.
+    fn sync_blocks(&mut self, memory: &mut Memory) {
+        if memory.get_generation() != self.block_generation {
+            self.blocks.fill(None);
+            self.block_coverage.fill(0);
+            self.block_generation = memory.get_generation();
+            memory.take_writes();
+            return;
+        }
+        if !memory.has_writes() {
+            return;
+        }
+        for address in memory.take_writes() {
+            if self.block_coverage[address] == 0 {
+                continue;
+            }
+            for start in address.saturating_sub(2 * MAX_BLOCK_LENGTH)..=address {
+                if self.blocks[start].as_ref().is_some_and(|block| address < block.end) {
+                    let block = self.blocks[start].take().unwrap();
+                    for byte in start..block.end {
+                        self.block_coverage[byte] -= 1;
                     }
                 }
-            },
-            0xF000 => {
-                match opcode & 0x00ff {
-                    0x0033 => {
-                        Instructions::Instructionfx33
-                    },
-                    0x0029 => {
-                        Instructions::Instructionfx29
-                    },
-                    0x0055 => {
-                        Instructions::Instructionfx55
-                    },
-                    0x0065 => {
-                        Instructions::Instructionfx65
-                    },
-                    0x001E => {
-                        Instructions::Instructionfx1e
-                    },
-                    0x000A => {
-                        Instructions::Instructionfx0a
-                    },
-                    0x0007 => {
-                        Instructions::Instructionfx07
-                    },
-                    0x0015 => {
-                        Instructions::Instructionfx15
-                    },
-                    0x0018 => {
-                        Instructions::Instructionfx18
-                    },
-                    _ => {
-                        panic!("Unknown Instruction (0xF000 family), opcode: 0x{:X}", opcode);
-                    }
+            }
+        }
+    }
+
+    fn fetch_decoded(&mut self, memory: &mut Memory) -> Result<Decoded, Fault> {
+        if !self.cache_enabled {
+            let opcode = self.fetch(memory, self.pc as usize)?;
+            return Ok(self.decode_operands(opcode));
+        }
+
+        if memory.get_generation() != self.cache_generation {
+            self.cache.fill(None);
+            self.cache_generation = memory.get_generation();
+            memory.take_writes();
+        } else if memory.has_writes() {
+            // Instructions are two bytes, a write also hits the one starting at the address before.
+            for address in memory.take_writes() {
+                self.cache[address] = None;
+                if address > 0 {
+                    self.cache[address - 1] = None;
                 }
             }
-            _ => {
-                panic!("Unknown Instruction, opcode: 0x{:X}", opcode);
+        }
+
+        let pc = self.pc as usize;
+        if let Some(Some(decoded)) = self.cache.get(pc) {
+            return Ok(*decoded);
+        }
+        let opcode = self.fetch(memory, self.pc as usize)?;
+        let decoded = self.decode_operands(opcode);
+        // Instructions running past the end of RAM aren't cached, their bytes come from two places.
+        if pc + 1 < self.cache.len() {
+            self.cache[pc] = Some(decoded);
+        }
+        return Ok(decoded);
+    }
+
+    fn decode_operands(&self, opcode: u16) -> Decoded {
+        return Decoded {
+            instruction: self.decode(opcode),
+            opcode,
+            x: (opcode & 0x0f00) >> 8,
+            y: (opcode & 0x00f0) >> 4,
+        };
+    }
+
+    // Accesses past the end of RAM that didn't fault wrapped around or were mirrored, which is usually
+    // a bug in the ROM. With tracing on, the first one of every instruction is reported while it runs.
+    fn report_wrap(&mut self, address: usize) {
+        if self.trace && address >= self.memory_size && self.wrapped.insert(self.pc) {
+            eprintln!("The instruction at 0x{:03X} accessed 0x{:X}, past the end of RAM, which ended up at 0x{:03X}.",
+                self.pc, address, address % self.memory_size);
+        }
+    }
+
+    fn fetch(&self, memory: &mut Memory, address: usize) -> Result<u16, Fault> {
+        let part1 = self.read(memory, address)? as u16;
+        let part2 = self.read(memory, address + 1)? as u16;
+        return Ok(part1 << 8 | part2);
+    }
+
+    fn decode(&self, opcode: u16) -> Instructions {
+        return Instructions::decode(opcode).unwrap_or_else(|| panic!("Unknown Instruction, opcode: 0x{:X}", opcode));
+    }
+
+    // Cxnn draws from this source, so runs with the same generator, seed and inputs are identical.
+    pub fn set_random_source(&mut self, rng: Box<dyn RandomSource>) {
+        self.rng = rng;
+    }
+    // A copy of the random source in its current state, to replay the same numbers later.
+    pub fn get_random_source(&self) -> Box<dyn RandomSource> {
+        return self.rng.clone_box();
+    }
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+    pub fn get_state(&self) -> CpuState {
+        return CpuState {
+            buffer: self.buffer,
+            pc: self.pc,
+            i: self.i,
+            sp: self.sp,
+            stack: self.stack.clone(),
+            v: self.v,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+        };
+    }
+    // A state saved with another stack mode (--stack-in-ram) or platform would leave SP pointing
+    // anywhere, it's rejected and the CPU is left as it was.
+    pub fn set_state(&mut self, state: &CpuState) -> Result<(), String> {
+        if self.stack_in_memory {
+            if !state.stack.is_empty() || state.sp > self.stack_address || !(self.stack_address - state.sp).is_multiple_of(2) {
+                return Err(format!("its stack pointer 0x{:03X} doesn't fit a stack in RAM below 0x{:03X}", state.sp, self.stack_address));
             }
+        } else if state.sp as usize != state.stack.len() {
+            return Err(format!("its stack pointer {} doesn't match its {} return addresses, was it saved with --stack-in-ram?", state.sp, state.stack.len()));
         }
+        self.buffer = state.buffer;
+        self.pc = state.pc;
+        self.i = state.i;
+        self.sp = state.sp;
+        self.stack = state.stack.clone();
+        self.v = state.v;
+        self.delay_timer = state.delay_timer;
+        self.sound_timer = state.sound_timer;
+        return Ok(());
+    }
+    // Back to the state the program starts in, the settings (quirks, stack, backend...) are kept.
+    pub fn reset(&mut self) {
+        self.buffer = [[false; WIDTH]; HEIGHT];
+        self.pc = self.load_address;
+        self.i = 0;
+        self.stack.clear();
+        self.sp = if self.stack_in_memory { self.stack_address } else { 0 };
+        self.v = [0; 16];
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.vblank = false;
+        self.waiting_for_vblank = false;
+        // The program may have been replaced along with the memory, whose generation can repeat.
+        self.cache_generation = u64::MAX;
+        self.block_generation = u64::MAX;
+        self.cache.fill(None);
+        self.blocks.fill(None);
+        self.block_coverage.fill(0);
+    }
+    // Depth is None for an unlimited stack, in_memory keeps it in RAM below the map's stack address.
+    pub fn set_stack(&mut self, depth: Option<usize>, in_memory: bool) {
+        self.stack_depth = depth;
+        self.stack_in_memory = in_memory;
+        self.stack.clear();
+        self.sp = if in_memory { self.stack_address } else { 0 };
+    }
+    // On by default, turning it off decodes every instruction again (e.g. to compare in benchmarks).
+    pub fn set_instruction_cache(&mut self, enabled: bool) {
+        self.cache_enabled = enabled;
+        self.cache_generation = u64::MAX;
+    }
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+        self.block_generation = u64::MAX;
+    }
+    pub fn get_cycles(&self) -> u64 {
+        return self.cycles;
+    }
+    // Times every instruction by opcode family, which slows execution down noticeably.
+    pub fn set_family_profiling(&mut self, enabled: bool) {
+        self.family_profile = if enabled { Some([(0, Duration::ZERO); 16]) } else { None };
+    }
+    pub fn get_family_profile(&self) -> Option<&FamilyProfile> {
+        return self.family_profile.as_ref();
+    }
+    pub fn set_profiler(&mut self, profiler: Option<Profiler>) {
+        self.profiler = profiler;
+    }
+    pub fn take_profiler(&mut self) -> Option<Profiler> {
+        return self.profiler.take();
+    }
+    pub fn set_coverage(&mut self, coverage: Option<Coverage>) {
+        self.coverage = coverage;
+    }
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        return self.coverage.take();
+    }
+    // Called on every 60Hz tick.
+    pub fn vblank(&mut self) {
+        self.vblank = self.waiting_for_vblank;
+        self.waiting_for_vblank = false;
+    }
+    // The rest of the frame can be skipped while waiting.
+    pub fn is_waiting_for_vblank(&self) -> bool {
+        return self.waiting_for_vblank;
+    }
+    pub fn tick_random_source(&mut self) {
+        self.rng.tick();
+    }
+    pub fn get_buffer(&self) -> &[[bool; WIDTH]; HEIGHT] {
+        return &self.buffer;
     }
     pub fn get_delay_timer(&self) -> u8 {
         return self.delay_timer;
@@ -0,0 +1,203 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::HashMap;
+use std::fs;
+
+use serde::Deserialize;
+use sha1::Digest;
+use sha1::Sha1;
+
+use super::display::parse_palette;
+use super::display::Palette;
+use super::platform::Platform;
+use super::quirks::Quirks;
+
+// Bundled database, in the same format as the community chip-8-database (sha1-hashes.json and programs.json)
+// so it can be swapped for the full one.
+const BUNDLED_HASHES: &str = include_str!("../../data/chip8-database/sha1-hashes.json");
+const BUNDLED_PROGRAMS: &str = include_str!("../../data/chip8-database/programs.json");
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    roms: HashMap<String, RomEntry>,
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct RomEntry {
+    #[serde(default)]
+    platforms: Vec<String>,
+    tickrate: Option<u32>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, QuirkOverrides>,
+    colors: Option<Colors>,
+    #[serde(default)]
+    keys: HashMap<String, u8>,
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct QuirkOverrides {
+    shift: Option<bool>,
+    memory_leave_i_unchanged: Option<bool>,
+    jump: Option<bool>,
+    logic: Option<bool>,
+    vblank: Option<bool>,
+    wrap: Option<bool>,
+}
+
+#[derive(Deserialize, Clone, Default)]
+struct Colors {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+// What the database knows about a ROM, everything the database doesn't specify is left to the defaults.
+pub struct RomSettings {
+    pub title: String,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub tickrate: u32,
+    pub palette: Option<Palette>,
+    // e.g. ("up", 0x5), used to map controllers
+    pub keys: Vec<(String, u8)>,
+}
+
+pub struct RomDatabase {
+    hashes: HashMap<String, usize>,
+    programs: Vec<Program>,
+    // User overrides keyed by SHA-1, same fields as the "roms" entries of programs.json.
+    overrides: HashMap<String, RomEntry>,
+}
+
+impl Default for RomDatabase {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+impl RomDatabase {
+    pub fn new() -> Self {
+        Self {
+            hashes: serde_json::from_str(BUNDLED_HASHES).expect("Bundled ROM database is invalid."),
+            programs: serde_json::from_str(BUNDLED_PROGRAMS).expect("Bundled ROM database is invalid."),
+            overrides: HashMap::new(),
+        }
+    }
+
+    // Replaces the bundled database with a checkout of the community database, the bundled one stays
+    // if either file can't be read.
+    pub fn load(&mut self, directory: &str) -> Result<(), String> {
+        let read = |name: &str| {
+            let path = format!("{}/{}", directory, name);
+            return fs::read_to_string(&path).map_err(|error| format!("failed to read {}: {}", path, error));
+        };
+        let hashes = read("sha1-hashes.json")?;
+        let programs = read("programs.json")?;
+        let hashes = serde_json::from_str(&hashes).map_err(|error| format!("invalid {}/sha1-hashes.json: {}", directory, error))?;
+        let programs = serde_json::from_str(&programs).map_err(|error| format!("invalid {}/programs.json: {}", directory, error))?;
+        self.hashes = hashes;
+        self.programs = programs;
+        return Ok(());
+    }
+
+    // {
+    //   "<sha1>": { "platforms": ["superchip"], "tickrate": 30, "colors": { "pixels": ["#000000", "#ffffff"] } }
+    // }
+    pub fn load_overrides(&mut self, path: &str) -> Result<(), String> {
+        let content = fs::read_to_string(path).map_err(|error| format!("failed to read ROM overrides file {}: {}", path, error))?;
+        self.overrides = serde_json::from_str(&content).map_err(|error| format!("invalid ROM overrides file {}: {}", path, error))?;
+        return Ok(());
+    }
+
+    // ROMs are identified by their SHA-1, unknown ones are logged so they can be added.
+    pub fn identify(&self, rom: &[u8]) -> Option<RomSettings> {
+        let hash = format!("{:x}", Sha1::digest(rom));
+        let settings = self.lookup(&hash);
+        match settings.as_ref() {
+            Some(settings) => eprintln!("Found \"{}\" in the ROM database, platform: {}.", settings.title, settings.platform.id()),
+            None => eprintln!("Unknown ROM (SHA-1 {}), using defaults.", hash),
+        }
+        return settings;
+    }
+
+    pub fn lookup(&self, hash: &str) -> Option<RomSettings> {
+        let program = self.hashes.get(hash).and_then(|index| self.programs.get(*index));
+        let entry = program.and_then(|program| program.roms.get(hash));
+        let overrides = self.overrides.get(hash);
+        if entry.is_none() && overrides.is_none() {
+            return None;
+        }
+
+        // Fields set in the override file win over the database.
+        let mut rom = entry.cloned().unwrap_or_default();
+        if let Some(overrides) = overrides {
+            if !overrides.platforms.is_empty() {
+                rom.platforms = overrides.platforms.clone();
+            }
+            if overrides.tickrate.is_some() {
+                rom.tickrate = overrides.tickrate;
+            }
+            rom.quirky_platforms.extend(overrides.quirky_platforms.clone());
+            if overrides.colors.is_some() {
+                rom.colors = overrides.colors.clone();
+            }
+            rom.keys.extend(overrides.keys.clone());
+        }
+
+        // The first platform we know about is the preferred one.
+        let platform_id = rom.platforms.iter().find(|id| Platform::from_id(id).is_some());
+        let platform = platform_id.and_then(|id| Platform::from_id(id)).unwrap_or(Platform::OriginalChip8);
+
+        let mut quirks = platform.quirks();
+        if let Some(quirky) = platform_id.and_then(|id| rom.quirky_platforms.get(id)) {
+            if let Some(shift) = quirky.shift { quirks.shift = shift; }
+            if let Some(leave_i_unchanged) = quirky.memory_leave_i_unchanged { quirks.memory = !leave_i_unchanged; }
+            if let Some(jump) = quirky.jump { quirks.jump = jump; }
+            if let Some(logic) = quirky.logic { quirks.vf_reset = logic; }
+            if let Some(vblank) = quirky.vblank { quirks.display_wait = vblank; }
+            if let Some(wrap) = quirky.wrap { quirks.clip = !wrap; }
+        }
+
+        let palette = rom.colors.as_ref().and_then(|colors| {
+            if colors.pixels.len() < 2 {
+                return None;
+            }
+            parse_palette(&format!("{},{}", colors.pixels[0], colors.pixels[1])).ok()
+        });
+
+        let mut keys: Vec<(String, u8)> = rom.keys.into_iter().collect();
+        keys.sort();
+
+        return Some(RomSettings {
+            title: program.map(|program| program.title.clone()).unwrap_or_default(),
+            platform,
+            quirks,
+            tickrate: rom.tickrate.unwrap_or(platform.default_tickrate()),
+            palette,
+            keys,
+        });
+    }
+}
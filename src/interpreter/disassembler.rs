@@ -0,0 +1,73 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use super::cpu::Instructions;
+
+// Mnemonics in the style of Cowgod's Chip-8 Technical Reference, e.g. "LD V1, 0x0A" or "DRW V0, V1, 5".
+// Opcodes that aren't instructions come out as data words.
+pub fn disassemble(opcode: u16) -> String {
+    let x = (opcode & 0x0F00) >> 8;
+    let y = (opcode & 0x00F0) >> 4;
+    let n = opcode & 0x000F;
+    let nn = opcode & 0x00FF;
+    let nnn = opcode & 0x0FFF;
+    let Some(instruction) = Instructions::decode(opcode) else {
+        return format!("DW 0x{:04X}", opcode);
+    };
+    return match instruction {
+        Instructions::Instruction00e0 => "CLS".to_string(),
+        Instructions::Instruction00ee => "RET".to_string(),
+        Instructions::Instruction1nnn => format!("JP 0x{:03X}", nnn),
+        Instructions::Instruction2nnn => format!("CALL 0x{:03X}", nnn),
+        Instructions::Instruction3xnn => format!("SE V{:X}, 0x{:02X}", x, nn),
+        Instructions::Instruction4xnn => format!("SNE V{:X}, 0x{:02X}", x, nn),
+        Instructions::Instruction5xy0 => format!("SE V{:X}, V{:X}", x, y),
+        Instructions::Instruction6xnn => format!("LD V{:X}, 0x{:02X}", x, nn),
+        Instructions::Instruction7xnn => format!("ADD V{:X}, 0x{:02X}", x, nn),
+        Instructions::Instruction8xy0 => format!("LD V{:X}, V{:X}", x, y),
+        Instructions::Instruction8xy1 => format!("OR V{:X}, V{:X}", x, y),
+        Instructions::Instruction8xy2 => format!("AND V{:X}, V{:X}", x, y),
+        Instructions::Instruction8xy3 => format!("XOR V{:X}, V{:X}", x, y),
+        Instructions::Instruction8xy4 => format!("ADD V{:X}, V{:X}", x, y),
+        Instructions::Instruction8xy5 => format!("SUB V{:X}, V{:X}", x, y),
+        Instructions::Instruction8xy6 => format!("SHR V{:X}, V{:X}", x, y),
+        Instructions::Instruction8xy7 => format!("SUBN V{:X}, V{:X}", x, y),
+        Instructions::Instruction8xye => format!("SHL V{:X}, V{:X}", x, y),
+        Instructions::Instruction9xy0 => format!("SNE V{:X}, V{:X}", x, y),
+        Instructions::Instructionannn => format!("LD I, 0x{:03X}", nnn),
+        Instructions::Instructionbnnn => format!("JP V0, 0x{:03X}", nnn),
+        Instructions::Instructioncxnn => format!("RND V{:X}, 0x{:02X}", x, nn),
+        Instructions::Instructiondxyn => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        Instructions::Instructionex9e => format!("SKP V{:X}", x),
+        Instructions::Instructionexa1 => format!("SKNP V{:X}", x),
+        Instructions::Instructionfx07 => format!("LD V{:X}, DT", x),
+        Instructions::Instructionfx0a => format!("LD V{:X}, K", x),
+        Instructions::Instructionfx15 => format!("LD DT, V{:X}", x),
+        Instructions::Instructionfx18 => format!("LD ST, V{:X}", x),
+        Instructions::Instructionfx1e => format!("ADD I, V{:X}", x),
+        Instructions::Instructionfx29 => format!("LD F, V{:X}", x),
+        Instructions::Instructionfx30 => format!("LD HF, V{:X}", x),
+        Instructions::Instructionfx33 => format!("LD B, V{:X}", x),
+        Instructions::Instructionfx55 => format!("LD [I], V{:X}", x),
+        Instructions::Instructionfx65 => format!("LD V{:X}, [I]", x),
+    };
+}
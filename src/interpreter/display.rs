@@ -26,21 +26,29 @@ use sdl2::render::Canvas;
 use sdl2::Sdl;
 use sdl2::video::Window;
 
+pub const TITLE: &str = "CHIP-8 Interpreter";
+
 const WIDTH: u32 = 64;
 const HEIGHT: u32 = 32;
-const SCALE_FACTOR: u32 = 10;
+pub const DEFAULT_SCALE_FACTOR: u32 = 10;
+
+// Background, Foreground
+pub type Palette = [[u8; 3]; 2];
+pub const DEFAULT_PALETTE: Palette = [[0, 0, 0], [255, 255, 255]];
 
 pub struct Display {
-    canvas: Canvas<Window>
+    canvas: Canvas<Window>,
+    scale_factor: u32,
+    palette: Palette,
 }
 
 impl Display{
-    pub fn new(sdl_context: &Sdl) -> Self {
+    pub fn new(sdl_context: &Sdl, scale_factor: u32, palette: Palette) -> Self {
 
         let video = sdl_context.video().unwrap();
 
         let window = video
-            .window("CHIP-8 Interpreter", WIDTH * SCALE_FACTOR, HEIGHT * SCALE_FACTOR)
+            .window(TITLE, WIDTH * scale_factor, HEIGHT * scale_factor)
             .position_centered()
             .opengl()
             .build()
@@ -48,34 +56,54 @@ impl Display{
 
         let mut canvas: Canvas<Window> = window.into_canvas().build().unwrap();
 
-        canvas.set_draw_color(Color::RGB(0, 0, 0));
+        let [r, g, b] = palette[0];
+        canvas.set_draw_color(Color::RGB(r, g, b));
         canvas.clear();
 
         Self {
-            canvas: canvas,
+            canvas,
+            scale_factor,
+            palette,
         }
     }
 
+    pub fn set_title(&mut self, title: &str) {
+        self.canvas.window_mut().set_title(title).unwrap();
+    }
+
     pub fn draw(&mut self, pixels: &[[bool; WIDTH as usize]; HEIGHT as usize]) {
         self.canvas.clear();
         for y in 0..HEIGHT {
             for x in 0..WIDTH {
-                if pixels[y as usize][x as usize] {
-                    // Foreground
-                    self.canvas.set_draw_color(Color::RGB(255, 255, 255));
-                } else {
-                    // Background
-                    self.canvas.set_draw_color(Color::RGB(0, 0, 0));
-                }
-
-                let x1 = (x * SCALE_FACTOR) as i32;
-                let y1 = (y * SCALE_FACTOR) as i32;
-                let x2 = ((x + 1) * SCALE_FACTOR) as u32;
-                let y2 = ((y + 1) * SCALE_FACTOR) as u32;
+                let [r, g, b] = self.palette[pixels[y as usize][x as usize] as usize];
+                self.canvas.set_draw_color(Color::RGB(r, g, b));
+
+                let x1 = (x * self.scale_factor) as i32;
+                let y1 = (y * self.scale_factor) as i32;
+                let x2 = (x + 1) * self.scale_factor;
+                let y2 = (y + 1) * self.scale_factor;
 
                 self.canvas.fill_rect(Rect::new(x1, y1, x2, y2)).unwrap();
             }
         }
         self.canvas.present();
     }
-}
\ No newline at end of file
+}
+
+// "000000,ffffff" (background, foreground), with or without a leading #.
+pub fn parse_palette(value: &str) -> Result<Palette, String> {
+    let colors: Vec<&str> = value.split(',').map(|color| color.trim().trim_start_matches('#')).collect();
+    if colors.len() != 2 {
+        return Err(format!("palette must be two hex colours (background,foreground), got \"{}\"", value));
+    }
+
+    let mut palette = DEFAULT_PALETTE;
+    for (index, color) in colors.iter().enumerate() {
+        let rgb = match u32::from_str_radix(color, 16) {
+            Ok(rgb) if color.len() == 6 => rgb,
+            _ => return Err(format!("\"{}\" isn't a hex colour like ff8800", color)),
+        };
+        palette[index] = [(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8];
+    }
+    return Ok(palette);
+}
@@ -20,93 +20,398 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use std::collections::HashMap;
-use std::collections::HashSet;
+use std::collections::BTreeMap;
+use std::fs;
 
-use sdl2::keyboard::Keycode;
+use sdl2::controller::Axis;
+use sdl2::controller::Button;
+use sdl2::controller::GameController;
+use sdl2::keyboard::Scancode;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::cpu::HEIGHT;
+use super::cpu::WIDTH;
+use super::font::FONTSET;
+
+// Physical layout of the COSMAC VIP hex keypad, row by row.
+pub const KEYPAD_LAYOUT: [u8; 16] = [
+    0x1, 0x2, 0x3, 0xC,
+    0x4, 0x5, 0x6, 0xD,
+    0x7, 0x8, 0x9, 0xE,
+    0xA, 0x0, 0xB, 0xF,
+];
+
+// Bindings use scancodes (physical key positions), so the default 1234/QWER/ASDF/ZXCV block
+// stays in the same place on AZERTY, QWERTZ, Dvorak etc.
+const DEFAULT_BINDINGS: [Scancode; 16] = [
+    Scancode::Num1, Scancode::Num2, Scancode::Num3, Scancode::Num4,
+    Scancode::Q, Scancode::W, Scancode::E, Scancode::R,
+    Scancode::A, Scancode::S, Scancode::D, Scancode::F,
+    Scancode::Z, Scancode::X, Scancode::C, Scancode::V,
+];
+
+// The D-pad (and left stick) on 5/7/8/9 and the face buttons on 6/4 covers most games.
+const DEFAULT_PAD_BINDINGS: [(u8, PadInput); 10] = [
+    (0x5, PadInput::Button(Button::DPadUp)),
+    (0x7, PadInput::Button(Button::DPadLeft)),
+    (0x8, PadInput::Button(Button::DPadDown)),
+    (0x9, PadInput::Button(Button::DPadRight)),
+    (0x5, PadInput::Axis(Axis::LeftY, false)),
+    (0x7, PadInput::Axis(Axis::LeftX, false)),
+    (0x8, PadInput::Axis(Axis::LeftY, true)),
+    (0x9, PadInput::Axis(Axis::LeftX, true)),
+    (0x6, PadInput::Button(Button::A)),
+    (0x4, PadInput::Button(Button::B)),
+];
+
+// Fraction of the stick's range that's ignored before an axis counts as pressed.
+const DEFAULT_DEADZONE: f32 = 0.35;
+
+// keymap.toml, CHIP-8 keys are hex digits, host keys are SDL scancode names and
+// controller inputs are SDL game controller button names or axes with a direction ("leftx+", "lefty-"):
+//
+// [keys]
+// "5" = ["W", "Up"]
+//
+// [pad]
+// "5" = ["dpup", "lefty-"]
+//
+// [rom."pong.rom".keys]
+// "1" = ["Up"]
+// "4" = ["Down"]
+//
+// [rom."pong.rom".pad]
+// "1" = ["dpup"]
+// "4" = ["dpdown"]
+#[derive(Serialize, Deserialize, Default)]
+struct KeymapConfig {
+    #[serde(default)]
+    keys: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pad: BTreeMap<String, Vec<String>>,
+    deadzone: Option<f32>,
+    #[serde(default)]
+    rom: BTreeMap<String, RomKeymapConfig>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct RomKeymapConfig {
+    #[serde(default)]
+    keys: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pad: BTreeMap<String, Vec<String>>,
+    deadzone: Option<f32>,
+}
+
+#[derive(Clone, Copy)]
+enum PadInput {
+    Button(Button),
+    // true for the positive direction of the axis
+    Axis(Axis, bool),
+}
+
+impl PadInput {
+    fn from_name(name: &str) -> Option<Self> {
+        if let Some(axis) = name.strip_suffix('+') {
+            return Axis::from_string(axis).map(|axis| PadInput::Axis(axis, true));
+        }
+        if let Some(axis) = name.strip_suffix('-') {
+            return Axis::from_string(axis).map(|axis| PadInput::Axis(axis, false));
+        }
+        return Button::from_string(name).map(PadInput::Button);
+    }
+
+    fn is_active(&self, controller: &GameController, deadzone: f32) -> bool {
+        match self {
+            PadInput::Button(button) => controller.button(*button),
+            PadInput::Axis(axis, positive) => {
+                let value = controller.axis(*axis) as f32 / i16::MAX as f32;
+                if *positive { value > deadzone } else { value < -deadzone }
+            }
+        }
+    }
+}
 
 pub struct Keypad {
-    key_map: HashMap<u8, Keycode>,
-    keys_active: HashMap<Keycode, bool>,
-    prev_keys: HashSet<Keycode>
+    bindings: [Vec<Scancode>; 16],
+    pad_bindings: [Vec<PadInput>; 16],
+    deadzone: f32,
+    controllers: Vec<GameController>,
+    keys_active: [bool; 16],
+}
+
+impl Default for Keypad {
+    fn default() -> Self {
+        return Self::new();
+    }
 }
 
-// TODO: if you can improve this, please do. It was quite rushed because I just had to get it working.
 impl Keypad {
 
-    // dummy
     pub fn new() -> Self {
+        let mut bindings: [Vec<Scancode>; 16] = Default::default();
+        for (index, key) in KEYPAD_LAYOUT.iter().enumerate() {
+            bindings[*key as usize].push(DEFAULT_BINDINGS[index]);
+        }
+
+        let mut pad_bindings: [Vec<PadInput>; 16] = Default::default();
+        for (key, input) in DEFAULT_PAD_BINDINGS.iter() {
+            pad_bindings[*key as usize].push(*input);
+        }
+
         Self {
-            key_map: HashMap::from([
-                (0x0, Keycode::X),
-                (0x1, Keycode::Num1),
-                (0x2, Keycode::Num2),
-                (0x3, Keycode::Num3),
-                (0x4, Keycode::Q),
-                (0x5, Keycode::W),
-                (0x6, Keycode::E),
-                (0x7, Keycode::A),
-                (0x8, Keycode::S),
-                (0x9, Keycode::D),
-                (0xA, Keycode::Z),
-                (0xB, Keycode::C),
-                (0xC, Keycode::Num4),
-                (0xD, Keycode::R),
-                (0xE, Keycode::F),
-                (0xF, Keycode::V),
-            ]),                   
-            keys_active: HashMap::from([
-                (Keycode::X, false),
-                (Keycode::Num1, false),
-                (Keycode::Num2, false),
-                (Keycode::Num3, false),
-                (Keycode::Q, false),
-                (Keycode::W, false),
-                (Keycode::F, false),
-                (Keycode::A, false),
-                (Keycode::S, false),
-                (Keycode::D, false),
-                (Keycode::Z, false),
-                (Keycode::C, false),
-                (Keycode::Num4, false),
-                (Keycode::R, false),
-                (Keycode::E, false),
-                (Keycode::V, false),
-            ]),
-            prev_keys: HashSet::from([])      
+            bindings,
+            pad_bindings,
+            deadzone: DEFAULT_DEADZONE,
+            controllers: Vec::new(),
+            keys_active: [false; 16],
         }
     }
 
-    pub fn is_key_down(&mut self, e: &sdl2::EventPump) {
+    // Applies the global bindings of a keymap file, then the overrides for the given ROM (by file name).
+    // Invalid entries are reported and ignored, a file that can't be read or parsed changes nothing.
+    pub fn load_keymap(&mut self, path: &str, rom: &str) -> Result<(), String> {
+        let content = fs::read_to_string(path).map_err(|error| format!("failed to read keymap file {}: {}", path, error))?;
+        let config: KeymapConfig = toml::from_str(&content).map_err(|error| format!("invalid keymap file {}: {}", path, error))?;
+
+        self.apply_bindings(&config.keys);
+        self.apply_pad_bindings(&config.pad);
+        if let Some(deadzone) = config.deadzone {
+            self.deadzone = deadzone.clamp(0.0, 1.0);
+        }
+
+        if let Some(overrides) = config.rom.get(rom) {
+            self.apply_bindings(&overrides.keys);
+            self.apply_pad_bindings(&overrides.pad);
+            if let Some(deadzone) = overrides.deadzone {
+                self.deadzone = deadzone.clamp(0.0, 1.0);
+            }
+        }
+        return Ok(());
+    }
+
+    // Writes the current bindings back to a keymap file, keeping other ROMs' overrides intact.
+    // If the ROM already has overrides, those are updated instead of the global bindings.
+    // An existing file that can't be read or parsed is left alone, it would lose those overrides.
+    pub fn save_keymap(&self, path: &str, rom: &str) -> Result<(), String> {
+        let mut config: KeymapConfig = match fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content).map_err(|error| format!("invalid keymap file {}, not overwriting it: {}", path, error))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => KeymapConfig::default(),
+            Err(error) => return Err(format!("failed to read keymap file {}: {}", path, error)),
+        };
+
+        let mut keys = BTreeMap::new();
+        for (key, scancodes) in self.bindings.iter().enumerate() {
+            keys.insert(format!("{:X}", key), scancodes.iter().map(|scancode| scancode.name().to_string()).collect());
+        }
+
+        match config.rom.get_mut(rom) {
+            Some(overrides) => overrides.keys = keys,
+            None => config.keys = keys,
+        }
 
-        let keys = e
-            .keyboard_state()
-            .pressed_scancodes()
-            .filter_map(Keycode::from_scancode)
-            .collect();
+        let content = toml::to_string_pretty(&config).expect("Failed to serialize keymap.");
+        fs::write(path, content).map_err(|error| format!("failed to write keymap file {}: {}", path, error))?;
+        eprintln!("Saved keymap to {}.", path);
+        return Ok(());
+    }
 
-        let new_keys: HashSet<Keycode> = &keys - &self.prev_keys;
-        let old_keys: HashSet<Keycode> = &self.prev_keys - &keys;
+    fn apply_bindings(&mut self, keys: &BTreeMap<String, Vec<String>>) {
+        for (key, names) in keys.iter() {
+            let key = match u8::from_str_radix(key, 16) {
+                Ok(key) if key < 16 => key,
+                _ => {
+                    eprintln!("Ignoring keymap entry for unknown CHIP-8 key \"{}\".", key);
+                    continue;
+                }
+            };
 
-        for new_key in new_keys.iter() {
-            *self.keys_active.get_mut(new_key).unwrap() = true;
+            let mut scancodes = Vec::new();
+            for name in names.iter() {
+                match Scancode::from_name(name) {
+                    Some(scancode) => scancodes.push(scancode),
+                    None => eprintln!("Ignoring unknown key \"{}\" bound to CHIP-8 key {:X}.", name, key),
+                }
+            }
+            self.bindings[key as usize] = scancodes;
         }
+    }
+
+    fn apply_pad_bindings(&mut self, pad: &BTreeMap<String, Vec<String>>) {
+        for (key, names) in pad.iter() {
+            let key = match u8::from_str_radix(key, 16) {
+                Ok(key) if key < 16 => key,
+                _ => {
+                    eprintln!("Ignoring controller mapping for unknown CHIP-8 key \"{}\".", key);
+                    continue;
+                }
+            };
 
-        for old_key in old_keys.iter() {
-            *self.keys_active.get_mut(old_key).unwrap() = false;
+            let mut inputs = Vec::new();
+            for name in names.iter() {
+                match PadInput::from_name(name) {
+                    Some(input) => inputs.push(input),
+                    None => eprintln!("Ignoring unknown controller input \"{}\" bound to CHIP-8 key {:X}.", name, key),
+                }
+            }
+            self.pad_bindings[key as usize] = inputs;
+        }
+    }
+
+    // Key hints from the ROM database ("up" -> 0x5, "a" -> 0x6, ...) replace the default controller profile.
+    pub fn apply_key_hints(&mut self, hints: &[(String, u8)]) {
+        let mut pad_bindings: [Vec<PadInput>; 16] = Default::default();
+        for (name, key) in hints.iter() {
+            let inputs: &[PadInput] = match name.as_str() {
+                "up" => &[PadInput::Button(Button::DPadUp), PadInput::Axis(Axis::LeftY, false)],
+                "down" => &[PadInput::Button(Button::DPadDown), PadInput::Axis(Axis::LeftY, true)],
+                "left" => &[PadInput::Button(Button::DPadLeft), PadInput::Axis(Axis::LeftX, false)],
+                "right" => &[PadInput::Button(Button::DPadRight), PadInput::Axis(Axis::LeftX, true)],
+                "a" => &[PadInput::Button(Button::A)],
+                "b" => &[PadInput::Button(Button::B)],
+                _ => continue,
+            };
+            if *key < 16 {
+                pad_bindings[*key as usize].extend_from_slice(inputs);
+            }
         }
 
-        self.prev_keys = keys;
+        if pad_bindings.iter().any(|inputs| !inputs.is_empty()) {
+            self.pad_bindings = pad_bindings;
+        }
     }
 
+    // Controllers are handed over by the frontend as they get connected.
+    pub fn add_controller(&mut self, controller: GameController) {
+        eprintln!("Controller connected: {}", controller.name());
+        self.controllers.push(controller);
+    }
+
+    pub fn remove_controller(&mut self, instance_id: u32) {
+        self.controllers.retain(|controller| controller.instance_id() != instance_id);
+    }
+
+    pub fn bind(&mut self, key: u8, scancodes: Vec<Scancode>) {
+        self.bindings[key as usize] = scancodes;
+    }
+
+    pub fn get_bindings(&self, key: u8) -> &[Scancode] {
+        return &self.bindings[key as usize];
+    }
+
+    pub fn is_key_down(&mut self, e: &sdl2::EventPump) {
+        let keyboard = e.keyboard_state();
+        for key in 0..16 {
+            let keyboard_active = self.bindings[key].iter().any(|scancode| keyboard.is_scancode_pressed(*scancode));
+            let pad_active = self.pad_bindings[key].iter().any(|input| {
+                self.controllers.iter().any(|controller| input.is_active(controller, self.deadzone))
+            });
+            self.keys_active[key] = keyboard_active || pad_active;
+        }
+    }
+
+    // Keypad state as a bitmask, bit n is set while key n is held down.
+    pub fn get_state(&self) -> u16 {
+        let mut state: u16 = 0;
+        for (key, active) in self.keys_active.iter().enumerate() {
+            state |= (*active as u16) << key;
+        }
+        return state;
+    }
+
+    pub fn set_state(&mut self, state: u16) {
+        for (key, active) in self.keys_active.iter_mut().enumerate() {
+            *active = state & (1 << key) != 0;
+        }
+    }
+
+    // Returns the lowest CHIP-8 key currently held down.
     pub fn is_any_key_down_emulator(&mut self) -> (u8, bool) {
-        // TODO: implement
-        return (0x0, false);
+        match self.keys_active.iter().position(|active| *active) {
+            Some(key) => (key as u8, true),
+            None => (0x0, false),
+        }
     }
 
     pub fn is_key_down_emulator(&mut self, key_code: u8) -> bool {
-        let emu_key = self.key_map[&key_code];
-        return self.keys_active[&emu_key];
+        // Only the lower nibble is wired to the keypad.
+        return self.keys_active[(key_code & 0xF) as usize];
+    }
+
+}
+
+// In-app rebinding screen, walks through the keypad in its physical layout and
+// asks for a host key for every CHIP-8 key.
+pub struct Rebinder {
+    index: usize,
+}
+
+impl Default for Rebinder {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+impl Rebinder {
+    pub fn new() -> Self {
+        Self {
+            index: 0,
+        }
+    }
+
+    pub fn current(&self) -> u8 {
+        return KEYPAD_LAYOUT[self.index];
+    }
+
+    // Binds the host key to the current CHIP-8 key, returns true once every key has been visited.
+    pub fn bind(&mut self, keypad: &mut Keypad, scancode: Scancode) -> bool {
+        keypad.bind(self.current(), vec![scancode]);
+        return self.skip();
+    }
+
+    // Keeps the current bindings, returns true once every key has been visited.
+    pub fn skip(&mut self) -> bool {
+        self.index += 1;
+        return self.index == KEYPAD_LAYOUT.len();
     }
 
-}
\ No newline at end of file
+    pub fn prompt(&self, keypad: &Keypad) -> String {
+        let current: Vec<&str> = keypad.get_bindings(self.current()).iter().map(|scancode| scancode.name()).collect();
+        return format!(
+            "Press a key for CHIP-8 key {:X} (currently: {}) - Tab: keep, Esc: done",
+            self.current(),
+            current.join(", ")
+        );
+    }
+
+    // Draws the 4x4 keypad using the built-in font, with the key being rebound highlighted.
+    pub fn render(&self) -> [[bool; WIDTH]; HEIGHT] {
+        const CELL_WIDTH: usize = WIDTH / 4;
+        const CELL_HEIGHT: usize = HEIGHT / 4;
+
+        let mut buffer = [[false; WIDTH]; HEIGHT];
+        for (index, key) in KEYPAD_LAYOUT.iter().enumerate() {
+            let cell_x = (index % 4) * CELL_WIDTH;
+            let cell_y = (index / 4) * CELL_HEIGHT;
+            let highlighted = index == self.index;
+
+            for y in 0..CELL_HEIGHT {
+                for x in 0..CELL_WIDTH {
+                    buffer[cell_y + y][cell_x + x] = highlighted;
+                }
+            }
+
+            // Glyphs are 4x5, centered in the cell.
+            let glyph = &FONTSET[*key as usize * 5..*key as usize * 5 + 5];
+            for (y, row) in glyph.iter().enumerate() {
+                for x in 0..4 {
+                    if row & (0x80 >> x) != 0 {
+                        buffer[cell_y + 1 + y][cell_x + 6 + x] ^= true;
+                    }
+                }
+            }
+        }
+        return buffer;
+    }
+}
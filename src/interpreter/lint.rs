@@ -0,0 +1,364 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::Range;
+
+use super::analysis::Analysis;
+use super::analysis::EdgeKind;
+use super::cpu::Instructions;
+use super::platform::extension;
+use super::platform::Platform;
+
+// Checks run over the code reachable from the load address, see chip8-lint.
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+pub struct Diagnostic {
+    pub address: u16,
+    pub severity: Severity,
+    pub message: String,
+    pub suggestion: String,
+}
+
+// Value of I before an instruction, as far as it can be followed.
+#[derive(Clone, Copy, PartialEq)]
+enum Index {
+    Known(u16),
+    Unknown,
+}
+
+pub fn lint(rom: &[u8], platform: Platform) -> Vec<Diagnostic> {
+    let map = platform.memory_map();
+    let analysis = Analysis::new(rom, map.load_address);
+    let mut diagnostics = Vec::new();
+
+    let accesses = memory_accesses(&analysis, platform);
+    check_instructions(&analysis, platform, &accesses, &mut diagnostics);
+    check_stack(&analysis, platform, &mut diagnostics);
+    check_memory(&analysis, platform, &accesses, &mut diagnostics);
+    check_unreachable(&analysis, &accesses, &mut diagnostics);
+    for edge in analysis.outside.iter() {
+        diagnostics.push(Diagnostic {
+            address: edge.from,
+            severity: Severity::Warning,
+            message: format!("control flow leaves the ROM, to 0x{:03X}", edge.to),
+            suggestion: "check the target address, or the ROM is truncated".to_string(),
+        });
+    }
+
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.address, diagnostic.severity));
+    return diagnostics;
+}
+
+fn check_instructions(analysis: &Analysis, platform: Platform, accesses: &[Access], diagnostics: &mut Vec<Diagnostic>) {
+    let quirks = platform.quirks();
+    let schip = matches!(platform, Platform::Superchip1 | Platform::Superchip | Platform::XoChip);
+    for (address, opcode) in analysis.code.iter() {
+        let (x, y, n) = (opcode >> 8 & 0xF, opcode >> 4 & 0xF, opcode & 0xF);
+        let mut report = |severity, message: String, suggestion: &str| diagnostics.push(Diagnostic {
+            address: *address,
+            severity,
+            message,
+            suggestion: suggestion.to_string(),
+        });
+
+        // Self-modifying code, what runs isn't what's in the ROM.
+        if let Some(writer) = writer(accesses, *address).or(writer(accesses, address.wrapping_add(1))) {
+            report(Severity::Note, format!("{:04X} is overwritten at runtime by the instruction at 0x{:03X}, it can't be checked", opcode, writer),
+                "self-modifying code is fine, but make sure the patched instruction is valid on every platform");
+            continue;
+        }
+        let Some(instruction) = Instructions::decode(*opcode) else {
+            match extension(*opcode) {
+                Some((name, first)) if platform.extends(first) => {
+                    report(Severity::Warning, format!("{:04X} ({}) is a {} instruction this interpreter doesn't implement, it halts here", opcode, name, first.id()),
+                        "run the ROM in an interpreter with full SUPER-CHIP or XO-CHIP support");
+                },
+                Some((name, first)) => {
+                    report(Severity::Error, format!("{:04X} ({}) is a {} instruction, {} doesn't have it", opcode, name, first.id(), platform.id()),
+                        &format!("run as {} or a later platform, or this is data reached by mistake", first.id()));
+                },
+                None => {
+                    report(Severity::Error, format!("{:04X} isn't a valid instruction, it halts the interpreter", opcode),
+                        "this is probably data reached by mistake, or an instruction of another platform");
+                },
+            }
+            continue;
+        };
+        match instruction {
+            // The decoder ignores the bits that aren't operands, other interpreters don't.
+            Instructions::Instruction00e0 | Instructions::Instruction00ee if opcode & 0x0F00 != 0 => {
+                report(Severity::Warning, format!("{:04X} runs as {:04X} here, but it's a 0nnn machine code call", opcode, opcode & 0x00FF),
+                    "use 00E0 or 00EE");
+            },
+            Instructions::Instruction5xy0 | Instructions::Instruction9xy0 if n != 0 => {
+                report(Severity::Warning, format!("{:04X} runs as {:X}xy0 here, but it isn't a CHIP-8 instruction (XO-CHIP uses 5xy2 and 5xy3)", opcode, opcode >> 12),
+                    "clear the last nibble");
+            },
+            Instructions::Instructionfx30 if !schip => {
+                report(Severity::Warning, format!("Fx30 (big font) is a SUPER-CHIP instruction, {} doesn't have it", platform.id()),
+                    "use Fx29 and the small font, or run as superchip");
+            },
+            Instructions::Instruction8xy6 | Instructions::Instruction8xye if x != y => {
+                let current = if quirks.shift { "shifts VX in place" } else { "shifts VY into VX" };
+                report(Severity::Note, format!("8xy6 and 8xyE shift VY into VX on the COSMAC VIP but VX in place on SUPER-CHIP, {} {}", platform.id(), current),
+                    "shift a register into itself (8xx6, 8xxE) to behave the same everywhere");
+            },
+            Instructions::Instructionfx55 | Instructions::Instructionfx65 if uses_index_next(analysis, *address) => {
+                let current = if quirks.memory { "increments I" } else { "leaves I alone" };
+                report(Severity::Note, format!("Fx55 and Fx65 increment I on the COSMAC VIP but not on SUPER-CHIP, {} {} and I is used again before it's set", platform.id(), current),
+                    "set I again with Annn before relying on it afterwards");
+            },
+            Instructions::Instructionbnnn if x != 0 => {
+                let current = if quirks.jump { format!("jumps to {:03X} + V{:X}", opcode & 0xFFF, x) } else { format!("jumps to {:03X} + V0", opcode & 0xFFF) };
+                report(Severity::Warning, format!("Bnnn adds V0 on the COSMAC VIP but VX (here V{:X}) on SUPER-CHIP, {} {}", x, platform.id(), current),
+                    "keep the offset in V0 and the table at x00 so both agree");
+            },
+            Instructions::Instructionfx0a => {
+                report(Severity::Note, "Fx0A blocks until a key is pressed, nothing else runs in the meantime (the timers keep counting down)".to_string(),
+                    "poll with Ex9E/ExA1 in the main loop if the game should keep running, the COSMAC VIP also waits for the key to be released");
+            },
+            _ => {},
+        }
+    }
+}
+
+// Whether I is used after the instruction at `address` before something sets it again.
+// Calls are assumed to use it, as they usually draw.
+fn uses_index_next(analysis: &Analysis, address: u16) -> bool {
+    let mut visited = BTreeSet::new();
+    let mut pending = analysis.edges_from(address).map(|edge| edge.to).collect::<Vec<_>>();
+    while let Some(address) = pending.pop() {
+        if !visited.insert(address) {
+            continue;
+        }
+        let Some(opcode) = analysis.code.get(&address) else {
+            continue;
+        };
+        match Instructions::decode(*opcode) {
+            Some(Instructions::Instructionannn | Instructions::Instructionfx29 | Instructions::Instructionfx30) => continue,
+            Some(Instructions::Instructiondxyn | Instructions::Instructionfx1e | Instructions::Instructionfx33 |
+                Instructions::Instructionfx55 | Instructions::Instructionfx65 | Instructions::Instruction2nnn) => return true,
+            _ => pending.extend(analysis.edges_from(address).map(|edge| edge.to)),
+        }
+    }
+    return false;
+}
+
+// Recursion, or call chains deeper than the platform's stack.
+fn check_stack(analysis: &Analysis, platform: Platform, diagnostics: &mut Vec<Diagnostic>) {
+    let callees: BTreeMap<u16, BTreeSet<u16>> = std::iter::once(analysis.load_address)
+        .chain(analysis.subroutines.iter().copied())
+        .map(|entry| (entry, analysis.callees(entry)))
+        .collect();
+
+    // Depth first, the path holds the subroutines being visited.
+    fn depth(entry: u16, callees: &BTreeMap<u16, BTreeSet<u16>>, path: &mut Vec<u16>, depths: &mut BTreeMap<u16, usize>, cycles: &mut Vec<Vec<u16>>) -> usize {
+        if let Some(depth) = depths.get(&entry) {
+            return *depth;
+        }
+        if let Some(position) = path.iter().position(|visited| *visited == entry) {
+            cycles.push(path[position..].to_vec());
+            return 0;
+        }
+        path.push(entry);
+        let deepest = callees[&entry].iter().map(|callee| 1 + depth(*callee, callees, path, depths, cycles)).max().unwrap_or(0);
+        path.pop();
+        depths.insert(entry, deepest);
+        return deepest;
+    }
+
+    let mut depths = BTreeMap::new();
+    let mut cycles = Vec::new();
+    let deepest = depth(analysis.load_address, &callees, &mut Vec::new(), &mut depths, &mut cycles);
+
+    for cycle in cycles.iter() {
+        let chain = cycle.iter().chain(std::iter::once(&cycle[0])).map(|entry| format!("0x{:03X}", entry)).collect::<Vec<_>>().join(" -> ");
+        diagnostics.push(Diagnostic {
+            address: cycle[0],
+            severity: Severity::Warning,
+            message: format!("recursive calls ({}) can overflow the stack", chain),
+            suggestion: match platform.stack_depth() {
+                Some(limit) => format!("make sure the recursion ends within {} calls, or use a loop", limit),
+                None => "make sure the recursion ends, or use a loop".to_string(),
+            },
+        });
+    }
+    if let Some(limit) = platform.stack_depth().filter(|limit| cycles.is_empty() && deepest > *limit) {
+        diagnostics.push(Diagnostic {
+            address: analysis.load_address,
+            severity: Severity::Warning,
+            message: format!("calls nest up to {} deep, {} only has room for {} return addresses", deepest, platform.id(), limit),
+            suggestion: "flatten the deepest call chains".to_string(),
+        });
+    }
+}
+
+// Memory accessed by an instruction at a known I.
+struct Access {
+    address: u16,
+    range: Range<u16>,
+    write: bool,
+}
+
+// Follows I through the code, merging different values into Unknown, to find the memory instructions access.
+fn memory_accesses(analysis: &Analysis, platform: Platform) -> Vec<Access> {
+    let quirks = platform.quirks();
+
+    // Forward data flow of I, merging different values into Unknown.
+    let mut index: BTreeMap<u16, Index> = BTreeMap::from([(analysis.load_address, Index::Unknown)]);
+    let mut pending = vec![analysis.load_address];
+    while let Some(address) = pending.pop() {
+        let Some(opcode) = analysis.code.get(&address).copied() else {
+            continue;
+        };
+        let x = opcode >> 8 & 0xF;
+        let after = match (Instructions::decode(opcode), index[&address]) {
+            (Some(Instructions::Instructionannn), _) => Index::Known(opcode & 0xFFF),
+            (Some(Instructions::Instructionfx55 | Instructions::Instructionfx65), Index::Known(i)) if quirks.memory => Index::Known(i.wrapping_add(x + 1)),
+            (Some(Instructions::Instructionfx55 | Instructions::Instructionfx65), _) if quirks.memory => Index::Unknown,
+            (Some(Instructions::Instructionfx1e | Instructions::Instructionfx29 | Instructions::Instructionfx30), _) => Index::Unknown,
+            (_, before) => before,
+        };
+        for edge in analysis.edges_from(address) {
+            // The subroutine may have changed it by the time a call returns.
+            let value = if edge.kind == EdgeKind::Next && opcode & 0xF000 == 0x2000 { Index::Unknown } else { after };
+            let merged = match index.get(&edge.to) {
+                None => value,
+                Some(existing) if *existing == value => continue,
+                Some(_) => Index::Unknown,
+            };
+            if index.get(&edge.to) != Some(&merged) {
+                index.insert(edge.to, merged);
+                pending.push(edge.to);
+            }
+        }
+    }
+
+    let mut accesses = Vec::new();
+    for (address, opcode) in analysis.code.iter() {
+        let Index::Known(i) = index.get(address).copied().unwrap_or(Index::Unknown) else {
+            continue;
+        };
+        let (x, n) = (opcode >> 8 & 0xF, opcode & 0xF);
+        match Instructions::decode(*opcode) {
+            Some(Instructions::Instructiondxyn) => accesses.push(Access { address: *address, range: i..i.saturating_add(n.max(1)), write: false }),
+            Some(Instructions::Instructionfx65) => accesses.push(Access { address: *address, range: i..i.saturating_add(x + 1), write: false }),
+            Some(Instructions::Instructionfx55) => accesses.push(Access { address: *address, range: i..i.saturating_add(x + 1), write: true }),
+            Some(Instructions::Instructionfx33) => accesses.push(Access { address: *address, range: i..i.saturating_add(3), write: true }),
+            _ => {},
+        }
+    }
+    return accesses;
+}
+
+// The instruction writing to a byte, if any.
+fn writer(accesses: &[Access], byte: u16) -> Option<u16> {
+    return accesses.iter().find(|access| access.write && access.range.contains(&byte)).map(|access| access.address);
+}
+
+// Reads from memory nothing initializes and code flowing into sprite data.
+fn check_memory(analysis: &Analysis, platform: Platform, accesses: &[Access], diagnostics: &mut Vec<Diagnostic>) {
+    let map = platform.memory_map();
+    let font = map.font_address..map.big_font_address() + platform.font().big.len() as u16;
+    for access in accesses.iter().filter(|access| !access.write) {
+        let uninitialized = access.range.clone().find(|byte| !analysis.in_rom(*byte) && !font.contains(byte) && writer(accesses, *byte).is_none());
+        if let Some(byte) = uninitialized {
+            diagnostics.push(Diagnostic {
+                address: access.address,
+                severity: Severity::Warning,
+                message: format!("reads 0x{:03X}, which is outside the ROM and nothing seems to write there", byte),
+                suggestion: if byte < map.load_address {
+                    "the interpreter area below the ROM belongs to the interpreter, keep data in the ROM".to_string()
+                } else {
+                    "RAM past the ROM starts out zeroed here but not on every interpreter, initialize it first".to_string()
+                },
+            });
+        }
+    }
+
+    // Sprites and other data read from the ROM, code flowing into it is likely a bug.
+    let data = accesses.iter()
+        .filter(|access| !access.write && analysis.in_rom(access.range.start))
+        .map(|access| access.range.clone())
+        .collect::<Vec<_>>();
+    for edge in analysis.edges.iter() {
+        if !data.iter().any(|range| range.contains(&edge.to)) || data.iter().any(|range| range.contains(&edge.from)) {
+            continue;
+        }
+        let message = match edge.kind {
+            EdgeKind::Next | EdgeKind::Skip => format!("execution runs into data at 0x{:03X}", edge.to),
+            _ => format!("jumps into data at 0x{:03X}", edge.to),
+        };
+        diagnostics.push(Diagnostic {
+            address: edge.from,
+            severity: Severity::Warning,
+            message,
+            suggestion: "the target is read as a sprite or with Fx65, check the address or end the code before the data".to_string(),
+        });
+    }
+}
+
+// Runs of ROM bytes that are neither reachable code nor accessed at a known I: dead code, padding,
+// or data only read through a computed I (Fx1E, Fx29), which can't be told apart. Single bytes are
+// usually padding between sprites and left out.
+fn check_unreachable(analysis: &Analysis, accesses: &[Access], diagnostics: &mut Vec<Diagnostic>) {
+    let used = |byte: u16| analysis.code.contains_key(&byte) || analysis.code.contains_key(&byte.wrapping_sub(1))
+        || accesses.iter().any(|access| access.range.contains(&byte));
+    // In usize, a ROM filling XO-CHIP's 64K ends right at 0x10000.
+    let mut start = None;
+    for offset in 0..=analysis.rom.len() {
+        let byte = analysis.load_address as usize + offset;
+        match (start, offset < analysis.rom.len() && !used(byte as u16)) {
+            (None, true) => start = Some(byte),
+            (Some(first), false) if byte - first < 2 => start = None,
+            (Some(first), false) => {
+                diagnostics.push(Diagnostic {
+                    address: first as u16,
+                    severity: Severity::Note,
+                    message: format!("{} bytes (0x{:03X}-0x{:03X}) are never executed or read at a known address", byte - first, first, byte - 1),
+                    suggestion: "fine if it's data read through a computed I, otherwise it's dead code or padding that can go".to_string(),
+                });
+                start = None;
+            },
+            _ => {},
+        }
+    }
+}
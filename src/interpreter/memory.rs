@@ -20,67 +20,227 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use std::fs::File;
-use std::io::Read;
 use std::ops::Index;
 
-const FONTSET: [u8; 80] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-];
-
-const ROM_OFFSET: usize = 0x200;
-const ROM_OFFSET_MAX: usize = 0xFFF;
+use super::font::Font;
+use super::font::SMALL_SIZE;
+use super::rom::RomError;
+
+// Access the program has to the interpreter's region below the load address.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ReservedAccess {
+    ReadWrite,
+    // Writes are ignored.
+    ReadOnly,
+    // Reads return 0 (except for the font) and writes are ignored.
+    None,
+}
+
+pub const RESERVED_ACCESS: [&str; 3] = ["rw", "ro", "none"];
+
+impl ReservedAccess {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rw" => Some(ReservedAccess::ReadWrite),
+            "ro" => Some(ReservedAccess::ReadOnly),
+            "none" => Some(ReservedAccess::None),
+            _ => None,
+        }
+    }
+}
+
+// What happens to accesses past the end of RAM (e.g. I + n in Dxyn, Fx33, Fx55 or Fx65).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BoundsPolicy {
+    // I wraps around at the end of RAM, and so do accesses.
+    Wrap,
+    // I keeps its value, accesses see RAM repeated, like on hardware ignoring the upper address lines.
+    Mirror,
+    // The CPU halts with a memory fault.
+    Fault,
+}
+
+pub const BOUNDS_POLICIES: [&str; 3] = ["wrap", "mirror", "fault"];
+
+impl BoundsPolicy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "wrap" => Some(BoundsPolicy::Wrap),
+            "mirror" => Some(BoundsPolicy::Mirror),
+            "fault" => Some(BoundsPolicy::Fault),
+            _ => None,
+        }
+    }
+}
+
+// Layout of the address space, see Platform::memory_map.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MemoryMap {
+    pub size: usize,
+    // Programs are loaded and start executing here, everything below belongs to the interpreter.
+    pub load_address: u16,
+    pub font_address: u16,
+    // Top of the call stack when it's kept in RAM, it grows down from here.
+    pub stack_address: u16,
+    pub reserved: ReservedAccess,
+    pub bounds: BoundsPolicy,
+}
+
+impl MemoryMap {
+    // The big font (Fx30) follows the small one.
+    pub fn big_font_address(&self) -> u16 {
+        return self.font_address + SMALL_SIZE as u16;
+    }
+
+    pub fn max_rom_size(&self) -> usize {
+        return self.size - self.load_address as usize;
+    }
+}
 
 pub struct Memory {
-    ram: [u8; 4096],
+    ram: Vec<u8>,
+    map: MemoryMap,
+    font_size: usize,
+    // Accesses past the end of RAM that wrapped (or were mirrored), useful to ROM authors.
+    wraps: u64,
+    // Addresses written since the CPU last looked, so it can drop decoded instructions (self-modifying code).
+    writes: Vec<usize>,
+    // Bumped when RAM changes wholesale (ROM, font or save state loaded), invalidating everything.
+    generation: u64,
 }
 
+// Past this many pending writes everything is invalidated at once.
+const MAX_PENDING_WRITES: usize = 64;
+
 impl Memory {
-    pub fn new() -> Self {
+    pub fn new(map: MemoryMap) -> Self {
         Memory {
-            ram: [0; 4096],
+            ram: vec![0; map.size],
+            map,
+            font_size: 0,
+            wraps: 0,
+            writes: Vec::new(),
+            generation: 0,
         }
     }
 
-    pub fn initialize(&mut self) {
-        // copy FONTSET to ram at FONTSET (0x50).
-        self.ram[0..FONTSET.len()].copy_from_slice(&FONTSET);
+    pub fn initialize(&mut self, font: &Font) {
+        // copy the font to ram at the font address (0x000 unless the memory map says otherwise).
+        let small = self.map.font_address as usize;
+        let big = self.map.big_font_address() as usize;
+        self.ram[small..small + font.small.len()].copy_from_slice(&font.small);
+        self.ram[big..big + font.big.len()].copy_from_slice(&font.big);
+        self.font_size = font.size();
+        self.generation += 1;
     }
 
-    pub fn load_rom(&mut self, path: &str) {
-        let mut file = File::open(path).expect("Failed to load ROM from file, make sure the path is valid.");
-        let mut buffer = Vec::<u8>::new();
-        file.read_to_end(&mut buffer).expect("Failed ROM from file, aborting!");
-        // If the ROM goes beyond unallowed memory region, we'll panic.
-        if (ROM_OFFSET + ROM_OFFSET_MAX) >= buffer.len() {
-            // Load the program at 0x200 that's where we'll begin execution.
-            self.ram[ROM_OFFSET..ROM_OFFSET+buffer.len()].copy_from_slice(&buffer);
-        } else {
-            panic!("Failed to copy ROM to memory, exceeding maximum allowed ROM size (4096 bytes)");
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        // The ROM has to fit between the load address and the end of RAM.
+        let limit = self.map.max_rom_size();
+        if rom.len() > limit {
+            return Err(RomError::TooLarge { size: rom.len(), limit });
+        }
+        // Load the program at the load address (usually 0x200) that's where we'll begin execution.
+        let start = self.map.load_address as usize;
+        self.ram[start..start + rom.len()].copy_from_slice(rom);
+        self.generation += 1;
+        eprintln!("Loaded ROM at 0x{:03X}, Size: {:?} Bytes.", start, rom.len());
+        return Ok(());
+    }
+
+    // Writes the bytes where `rom` differs from the loaded `previous` ROM, bytes past the end of a
+    // shorter ROM are cleared. Returns the number of bytes written.
+    pub fn patch_rom(&mut self, previous: &[u8], rom: &[u8]) -> Result<usize, RomError> {
+        let limit = self.map.max_rom_size();
+        if rom.len() > limit {
+            return Err(RomError::TooLarge { size: rom.len(), limit });
+        }
+        let start = self.map.load_address as usize;
+        let mut patched = 0;
+        for offset in 0..rom.len().max(previous.len()) {
+            let byte = rom.get(offset).copied().unwrap_or(0);
+            if previous.get(offset).copied().unwrap_or(0) != byte {
+                self.ram[start + offset] = byte;
+                patched += 1;
+            }
         }
-        println!("Loaded ROM, Size: {:?} Bytes.", buffer.len());
+        // Cached instructions and blocks are dropped all at once.
+        self.generation += 1;
+        return Ok(patched);
     }
 
-    pub fn get_from_index(&mut self, i: usize) -> u8 {
-        return *self.ram.index(i);
+    pub fn get_map(&self) -> MemoryMap {
+        return self.map;
     }
 
-    pub fn set_from_index(&mut self, i: usize, data: u8) {
+    pub fn get_ram(&self) -> &[u8] {
+        return &self.ram;
+    }
+
+    pub fn set_ram(&mut self, ram: &[u8]) {
+        self.ram.copy_from_slice(ram);
+        self.generation += 1;
+    }
+
+    pub fn get_generation(&self) -> u64 {
+        return self.generation;
+    }
+
+    // Addresses written since the last call.
+    pub fn take_writes(&mut self) -> Vec<usize> {
+        return std::mem::take(&mut self.writes);
+    }
+
+    pub fn has_writes(&self) -> bool {
+        return !self.writes.is_empty();
+    }
+
+    pub fn get_wrap_count(&self) -> u64 {
+        return self.wraps;
+    }
+
+    // None if the address is out of bounds and the policy is to fault.
+    pub fn get_from_index(&mut self, i: usize) -> Option<u8> {
+        let i = self.resolve(i)?;
+        if self.map.reserved == ReservedAccess::None && self.is_reserved(i) && !self.is_font(i) {
+            return Some(0);
+        }
+        return Some(*self.ram.index(i));
+    }
+
+    // None if the address is out of bounds and the policy is to fault.
+    pub fn set_from_index(&mut self, i: usize, data: u8) -> Option<()> {
+        let i = self.resolve(i)?;
+        if self.map.reserved != ReservedAccess::ReadWrite && self.is_reserved(i) {
+            return Some(());
+        }
         self.ram[i] = data;
+        if self.writes.len() < MAX_PENDING_WRITES {
+            self.writes.push(i);
+        } else {
+            self.writes.clear();
+            self.generation += 1;
+        }
+        return Some(());
     }
-}
\ No newline at end of file
+
+    fn resolve(&mut self, i: usize) -> Option<usize> {
+        if i < self.ram.len() {
+            return Some(i);
+        }
+        if self.map.bounds == BoundsPolicy::Fault {
+            return None;
+        }
+        self.wraps += 1;
+        return Some(i % self.ram.len());
+    }
+
+    fn is_reserved(&self, i: usize) -> bool {
+        return i < self.map.load_address as usize;
+    }
+
+    fn is_font(&self, i: usize) -> bool {
+        let font = self.map.font_address as usize;
+        return (font..font + self.font_size).contains(&i);
+    }
+}
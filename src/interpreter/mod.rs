@@ -1,4 +1,22 @@
 pub mod memory;
 pub mod cpu;
 pub mod display;
-pub mod keypad;
\ No newline at end of file
+pub mod keypad;
+pub mod recorder;
+pub mod audio;
+pub mod movie;
+pub mod random;
+pub mod quirks;
+pub mod state;
+pub mod platform;
+pub mod database;
+pub mod rom;
+pub mod font;
+pub mod bench;
+pub mod disassembler;
+pub mod profiler;
+pub mod coverage;
+pub mod analysis;
+pub mod lint;
+pub mod cfg;
+pub mod watcher;
@@ -0,0 +1,175 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::fs;
+
+use super::cpu::HEIGHT;
+use super::cpu::WIDTH;
+use super::platform::Platform;
+use super::quirks::Quirks;
+use super::quirks::QUIRKS;
+
+const MAGIC: &str = "chip8-movie 1";
+
+// A movie is the RNG seed plus the keypad state of every emulated frame, which is all
+// that's needed to reproduce a session. The settings that change how the ROM runs are
+// stored as well, a replay with other settings would go its own way. The hash of the
+// final framebuffer is stored so a replay can verify it ended up in the same place.
+//
+// chip8-movie 1
+// rng default
+// seed 1234
+// platform originalChip8
+// ips 15
+// quirks vf-reset=on memory=on shift=off jump=off display-wait=on clip=on
+// hash 9f1c...
+// 0000
+// 0020
+// ...
+pub struct Movie {
+    pub rng: String,
+    pub seed: u64,
+    pub platform: Platform,
+    pub ips: u32,
+    pub quirks: Quirks,
+    pub hash: Option<u64>,
+    pub inputs: Vec<u16>,
+}
+
+impl Movie {
+    pub fn new(rng: &str, seed: u64, platform: Platform, ips: u32, quirks: Quirks) -> Self {
+        Self {
+            rng: rng.to_string(),
+            seed,
+            platform,
+            ips,
+            quirks,
+            hash: None,
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|error| format!("failed to read {}: {}", path, error))?;
+        let mut lines = content.lines();
+
+        if lines.next() != Some(MAGIC) {
+            return Err(format!("{} is not a movie file", path));
+        }
+
+        let invalid = |what: &str, line: &str| format!("invalid {} \"{}\" in {}", what, line, path);
+        let (mut rng, mut seed, mut platform, mut ips, mut quirks) = (None, None, None, None, None);
+        let mut hash = None;
+        let mut inputs = Vec::new();
+        for line in lines {
+            if let Some(value) = line.strip_prefix("rng ") {
+                rng = Some(value.to_string());
+            } else if let Some(value) = line.strip_prefix("seed ") {
+                seed = Some(value.parse().map_err(|_| invalid("seed", value))?);
+            } else if let Some(value) = line.strip_prefix("platform ") {
+                platform = Some(Platform::from_id(value).ok_or(invalid("platform", value))?);
+            } else if let Some(value) = line.strip_prefix("ips ") {
+                ips = Some(value.parse().map_err(|_| invalid("ips", value))?);
+            } else if let Some(value) = line.strip_prefix("quirks ") {
+                quirks = Some(parse_quirks(value).ok_or(invalid("quirks", value))?);
+            } else if let Some(value) = line.strip_prefix("hash ") {
+                hash = Some(u64::from_str_radix(value, 16).map_err(|_| invalid("hash", value))?);
+            } else {
+                inputs.push(u16::from_str_radix(line, 16).map_err(|_| invalid("input", line))?);
+            }
+        }
+
+        let missing = |what: &str| format!("{} has no {} line", path, what);
+        let mut movie = Movie::new(&rng.ok_or(missing("rng"))?, seed.ok_or(missing("seed"))?,
+            platform.ok_or(missing("platform"))?, ips.ok_or(missing("ips"))?, quirks.ok_or(missing("quirks"))?);
+        movie.hash = hash;
+        movie.inputs = inputs;
+        return Ok(movie);
+    }
+
+    // The first setting of this run that differs from the recording, with the option that fixes it.
+    pub fn check(&self, platform: Platform, ips: u32, quirks: Quirks) -> Result<(), String> {
+        if platform != self.platform {
+            return Err(format!("it was recorded on {} and this run uses {}, add --platform {}", self.platform.id(), platform.id(), self.platform.id()));
+        }
+        if ips != self.ips {
+            return Err(format!("it was recorded at {} instructions per frame and this run uses {}, add --ips {}", self.ips, ips, self.ips));
+        }
+        for name in QUIRKS {
+            let (recorded, current) = (self.quirks.get(name).unwrap(), quirks.get(name).unwrap());
+            if recorded != current {
+                return Err(format!("it was recorded with the {} quirk {} and this run has it {}, add --quirk {}={}",
+                    name, on_off(recorded), on_off(current), name, on_off(recorded)));
+            }
+        }
+        return Ok(());
+    }
+
+    pub fn save(&self, path: &str) {
+        let quirks = QUIRKS.iter().map(|name| format!("{}={}", name, on_off(self.quirks.get(name).unwrap()))).collect::<Vec<_>>().join(" ");
+        let mut content = format!("{}\nrng {}\nseed {}\nplatform {}\nips {}\nquirks {}\n",
+            MAGIC, self.rng, self.seed, self.platform.id(), self.ips, quirks);
+        if let Some(hash) = self.hash {
+            content += &format!("hash {:016x}\n", hash);
+        }
+        for input in self.inputs.iter() {
+            content += &format!("{:04x}\n", input);
+        }
+        fs::write(path, content).expect("Failed to write movie file.");
+        eprintln!("Saved movie ({} frames) to {}.", self.inputs.len(), path);
+    }
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    return if enabled { "on" } else { "off" };
+}
+
+// "vf-reset=on memory=on ...", every quirk has to be there.
+fn parse_quirks(value: &str) -> Option<Quirks> {
+    let mut quirks = Quirks::new();
+    let mut seen = 0;
+    for setting in value.split_whitespace() {
+        let (name, enabled) = setting.split_once('=')?;
+        let enabled = match enabled {
+            "on" => true,
+            "off" => false,
+            _ => return None,
+        };
+        if !quirks.set(name, enabled) {
+            return None;
+        }
+        seen += 1;
+    }
+    return if seen == QUIRKS.len() { Some(quirks) } else { None };
+}
+
+// FNV-1a, stable across platforms and Rust versions unlike std's hashers.
+pub fn framebuffer_hash(pixels: &[[bool; WIDTH]; HEIGHT]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for row in pixels.iter() {
+        for pixel in row.iter() {
+            hash ^= *pixel as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    return hash;
+}
@@ -0,0 +1,182 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use super::font::Font;
+use super::memory::BoundsPolicy;
+use super::memory::MemoryMap;
+use super::memory::ReservedAccess;
+use super::quirks::Quirks;
+
+// CHIP-8 variants, named after the platform ids used by the community chip-8-database.
+// Source: https://github.com/chip-8/chip-8-database
+// The ETI-660 isn't in the database, it has to be selected with --platform or a ROM override.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Platform {
+    OriginalChip8,
+    HybridVip,
+    Eti660,
+    ModernChip8,
+    Chip48,
+    Superchip1,
+    Superchip,
+    XoChip,
+}
+
+pub const PLATFORMS: [&str; 8] = [
+    "originalChip8",
+    "hybridVIP",
+    "eti660",
+    "modernChip8",
+    "chip48",
+    "superchip1",
+    "superchip",
+    "xochip",
+];
+
+// SUPER-CHIP and XO-CHIP instructions this interpreter doesn't run (there's no hi-res mode,
+// scrolling, flag registers or XO-CHIP audio), as mask, value, name and the first platform with them.
+const EXTENSIONS: [(u16, u16, &str, Platform); 12] = [
+    (0xFFFF, 0x00FD, "exit", Platform::Superchip1),
+    (0xFFFF, 0x00FE, "low resolution", Platform::Superchip1),
+    (0xFFFF, 0x00FF, "high resolution", Platform::Superchip1),
+    (0xF0FF, 0xF075, "save flags", Platform::Superchip1),
+    (0xF0FF, 0xF085, "load flags", Platform::Superchip1),
+    (0xFFF0, 0x00C0, "scroll down", Platform::Superchip),
+    (0xFFFF, 0x00FB, "scroll right", Platform::Superchip),
+    (0xFFFF, 0x00FC, "scroll left", Platform::Superchip),
+    (0xFFF0, 0x00D0, "scroll up", Platform::XoChip),
+    (0xF0FF, 0xF001, "select planes", Platform::XoChip),
+    (0xFFFF, 0xF002, "load audio pattern", Platform::XoChip),
+    (0xF0FF, 0xF03A, "set pitch", Platform::XoChip),
+];
+
+// The name of a SUPER-CHIP or XO-CHIP instruction and the first platform that has it.
+pub fn extension(opcode: u16) -> Option<(&'static str, Platform)> {
+    return EXTENSIONS.iter()
+        .find(|(mask, value, _, _)| opcode & mask == *value)
+        .map(|(_, _, name, first)| (*name, *first));
+}
+
+impl Platform {
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "originalChip8" => Some(Platform::OriginalChip8),
+            "hybridVIP" => Some(Platform::HybridVip),
+            "eti660" => Some(Platform::Eti660),
+            "modernChip8" => Some(Platform::ModernChip8),
+            "chip48" => Some(Platform::Chip48),
+            "superchip1" => Some(Platform::Superchip1),
+            "superchip" => Some(Platform::Superchip),
+            "xochip" => Some(Platform::XoChip),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Platform::OriginalChip8 => "originalChip8",
+            Platform::HybridVip => "hybridVIP",
+            Platform::Eti660 => "eti660",
+            Platform::ModernChip8 => "modernChip8",
+            Platform::Chip48 => "chip48",
+            Platform::Superchip1 => "superchip1",
+            Platform::Superchip => "superchip",
+            Platform::XoChip => "xochip",
+        }
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Platform::OriginalChip8 | Platform::HybridVip | Platform::Eti660 => Quirks::from_preset("chip8").unwrap(),
+            Platform::ModernChip8 => Quirks {
+                vf_reset: false,
+                memory: true,
+                shift: false,
+                jump: false,
+                display_wait: false,
+                clip: true,
+            },
+            Platform::Chip48 | Platform::Superchip1 | Platform::Superchip => Quirks::from_preset("schip").unwrap(),
+            Platform::XoChip => Quirks::from_preset("xochip").unwrap(),
+        }
+    }
+
+    // Whether the platform has the instructions `first` introduced, each one extends the previous one.
+    pub fn extends(&self, first: Platform) -> bool {
+        match first {
+            Platform::Superchip1 => matches!(self, Platform::Superchip1 | Platform::Superchip | Platform::XoChip),
+            Platform::Superchip => matches!(self, Platform::Superchip | Platform::XoChip),
+            _ => *self == first,
+        }
+    }
+
+    // Instructions per frame.
+    pub fn default_tickrate(&self) -> u32 {
+        match self {
+            Platform::OriginalChip8 | Platform::HybridVip | Platform::Eti660 => 15,
+            Platform::ModernChip8 => 12,
+            Platform::Chip48 | Platform::Superchip1 | Platform::Superchip => 30,
+            Platform::XoChip => 100,
+        }
+    }
+
+    // Nested calls, None is unlimited.
+    pub fn stack_depth(&self) -> Option<usize> {
+        match self {
+            Platform::OriginalChip8 | Platform::HybridVip | Platform::Eti660 => Some(12),
+            Platform::ModernChip8 | Platform::Chip48 | Platform::Superchip1 | Platform::Superchip => Some(16),
+            Platform::XoChip => None,
+        }
+    }
+
+    pub fn font(&self) -> Font {
+        let name = match self {
+            Platform::OriginalChip8 | Platform::HybridVip => "vip",
+            Platform::Eti660 => "eti660",
+            Platform::Chip48 | Platform::Superchip1 | Platform::Superchip => "schip",
+            Platform::ModernChip8 | Platform::XoChip => "octo",
+        };
+        return Font::from_name(name).unwrap();
+    }
+
+    // The VIP, ETI-660 and HP48 interpreters all kept the font outside of the program's address space,
+    // it's placed at 0x000 here. CHIP-8 and SUPER-CHIP have 4K of RAM, XO-CHIP 64K.
+    pub fn memory_map(&self) -> MemoryMap {
+        let map = MemoryMap {
+            size: 0x1000,
+            load_address: 0x200,
+            font_address: 0x000,
+            // The VIP interpreter's stack, 0xEA0-0xECF.
+            stack_address: 0xED0,
+            reserved: ReservedAccess::ReadWrite,
+            bounds: BoundsPolicy::Wrap,
+        };
+        match self {
+            // The VIP and ETI-660 ignore the address lines above their RAM.
+            Platform::OriginalChip8 | Platform::HybridVip => MemoryMap { bounds: BoundsPolicy::Mirror, ..map },
+            // The ETI-660's monitor occupies everything up to 0x600.
+            Platform::Eti660 => MemoryMap { load_address: 0x600, bounds: BoundsPolicy::Mirror, ..map },
+            Platform::XoChip => MemoryMap { size: 0x10000, ..map },
+            _ => map,
+        }
+    }
+}
@@ -0,0 +1,171 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+use super::disassembler::disassemble;
+
+// Instructions taking at least this share of all cycles are marked as hot.
+const HOT_SHARE: f64 = 0.01;
+const HOT_SPOTS: usize = 20;
+
+#[derive(Default)]
+struct Subroutine {
+    calls: u64,
+    // Cycles spent in the subroutine itself, and including the subroutines it called.
+    self_cycles: u64,
+    total_cycles: u64,
+}
+
+// Counts the cycles spent on every address and in every subroutine, following 2nnn and 00EE.
+pub struct Profiler {
+    cycles: Vec<u64>,
+    // Last opcode executed at every address, for the disassembly.
+    opcodes: Vec<u16>,
+    total: u64,
+    // Entry addresses of the subroutines being executed, innermost last. The program itself is the root.
+    calls: Vec<u16>,
+    subroutines: HashMap<u16, Subroutine>,
+    // Every call stack seen so far with its cycles, and the index of the current one.
+    stacks: Vec<(Vec<u16>, u64)>,
+    stack_indices: HashMap<Vec<u16>, usize>,
+    stack: usize,
+}
+
+impl Profiler {
+    pub fn new(memory_size: usize) -> Self {
+        Profiler {
+            cycles: vec![0; memory_size],
+            opcodes: vec![0; memory_size],
+            total: 0,
+            calls: Vec::new(),
+            subroutines: HashMap::new(),
+            stacks: vec![(Vec::new(), 0)],
+            stack_indices: HashMap::from([(Vec::new(), 0)]),
+            stack: 0,
+        }
+    }
+
+    // Called for every executed instruction, with the address it was executed at.
+    pub fn record(&mut self, pc: u16, opcode: u16) {
+        let address = pc as usize % self.cycles.len();
+        self.cycles[address] += 1;
+        self.opcodes[address] = opcode;
+        self.total += 1;
+        self.stacks[self.stack].1 += 1;
+
+        if let Some(current) = self.calls.last() {
+            self.subroutines.get_mut(current).unwrap().self_cycles += 1;
+        }
+        // Recursive subroutines count once.
+        for (depth, entry) in self.calls.iter().enumerate() {
+            if !self.calls[..depth].contains(entry) {
+                self.subroutines.get_mut(entry).unwrap().total_cycles += 1;
+            }
+        }
+
+        if opcode & 0xF000 == 0x2000 {
+            let entry = opcode & 0x0FFF;
+            self.subroutines.entry(entry).or_default().calls += 1;
+            self.calls.push(entry);
+            self.switch_stack();
+        } else if opcode == 0x00EE && self.calls.pop().is_some() {
+            // Returns without a call (e.g. after loading a save state) are ignored.
+            self.switch_stack();
+        }
+    }
+
+    fn switch_stack(&mut self) {
+        self.stack = match self.stack_indices.get(&self.calls) {
+            Some(index) => *index,
+            None => {
+                self.stacks.push((self.calls.clone(), 0));
+                self.stack_indices.insert(self.calls.clone(), self.stacks.len() - 1);
+                self.stacks.len() - 1
+            },
+        };
+    }
+
+    fn share(&self, cycles: u64) -> f64 {
+        return cycles as f64 / self.total.max(1) as f64;
+    }
+
+    // Flat profile, subroutines and the disassembly of all executed code with its cycles.
+    pub fn report(&self) -> String {
+        let mut report = format!("{} cycles\n\nHot spots\n", self.total);
+        let addresses = (0..self.cycles.len()).filter(|address| self.cycles[*address] > 0).collect::<Vec<_>>();
+        let mut hottest = addresses.clone();
+        hottest.sort_by_key(|address| std::cmp::Reverse(self.cycles[*address]));
+        report += &format!("{:<8} {:>12} {:>7}  {}\n", "address", "cycles", "share", "instruction");
+        for address in hottest.iter().take(HOT_SPOTS) {
+            let cycles = self.cycles[*address];
+            report += &format!("0x{:03X}    {:>12} {:>6.2}%  {}\n", address, cycles, 100.0 * self.share(cycles), disassemble(self.opcodes[*address]));
+        }
+
+        report += "\nSubroutines\n";
+        report += &format!("{:<8} {:>10} {:>12} {:>7} {:>12} {:>7}\n", "address", "calls", "self", "share", "total", "share");
+        let mut subroutines = self.subroutines.iter().collect::<Vec<_>>();
+        subroutines.sort_by_key(|(entry, subroutine)| (std::cmp::Reverse(subroutine.total_cycles), **entry));
+        for (entry, subroutine) in subroutines {
+            report += &format!("0x{:03X}    {:>10} {:>12} {:>6.2}% {:>12} {:>6.2}%\n", entry, subroutine.calls,
+                subroutine.self_cycles, 100.0 * self.share(subroutine.self_cycles),
+                subroutine.total_cycles, 100.0 * self.share(subroutine.total_cycles));
+        }
+
+        report += "\nDisassembly (* hot)\n";
+        let mut previous = None;
+        for address in addresses {
+            if previous.is_some_and(|previous| address > previous + 2) {
+                report += "         ...\n";
+            }
+            let cycles = self.cycles[address];
+            let share = self.share(cycles);
+            let hot = if share >= HOT_SHARE { "*" } else { " " };
+            let bar = "#".repeat((share * 50.0).ceil() as usize);
+            report += &format!("{} 0x{:03X}  {:04X}  {:<16} {:>12} {:>6.2}% {}\n",
+                hot, address, self.opcodes[address], disassemble(self.opcodes[address]), cycles, 100.0 * share, bar);
+            previous = Some(address);
+        }
+        return report;
+    }
+
+    // One line per call stack, "main;sub_2A4;sub_300 1234", as read by flamegraph.pl, inferno or speedscope.
+    pub fn collapsed_stacks(&self) -> String {
+        let mut collapsed = String::new();
+        for (calls, cycles) in self.stacks.iter().filter(|(_, cycles)| *cycles > 0) {
+            let frames = calls.iter().map(|entry| format!("sub_{:03X}", entry));
+            collapsed += &std::iter::once("main".to_string()).chain(frames).collect::<Vec<_>>().join(";");
+            collapsed += &format!(" {}\n", cycles);
+        }
+        return collapsed;
+    }
+
+    pub fn save_report(&self, path: &str) -> io::Result<()> {
+        return fs::write(path, self.report());
+    }
+
+    pub fn save_collapsed_stacks(&self, path: &str) -> io::Result<()> {
+        return fs::write(path, self.collapsed_stacks());
+    }
+}
@@ -0,0 +1,123 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// Behaviours that differ between CHIP-8 implementations.
+// Source: https://github.com/Timendus/chip8-test-suite#quirks-test
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Quirks {
+    // 8xy1, 8xy2 and 8xy3 reset VF to 0.
+    pub vf_reset: bool,
+    // Fx55 and Fx65 increment I by x + 1.
+    pub memory: bool,
+    // 8xy6 and 8xyE shift vX in place instead of shifting vY into vX.
+    pub shift: bool,
+    // Bnnn jumps to xnn + vX instead of nnn + v0.
+    pub jump: bool,
+    // Dxyn waits for the next 60Hz tick (vertical blank) before drawing, limiting drawing to one sprite per frame.
+    pub display_wait: bool,
+    // Sprites are clipped at the edges of the screen instead of wrapping around (the start position always wraps).
+    pub clip: bool,
+}
+
+pub const PRESETS: [&str; 3] = ["chip8", "schip", "xochip"];
+
+// Names used to turn single quirks on or off (--quirk display-wait=off).
+pub const QUIRKS: [&str; 6] = ["vf-reset", "memory", "shift", "jump", "display-wait", "clip"];
+
+impl Default for Quirks {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+impl Quirks {
+    // How this interpreter behaved before quirks could be configured, the default for ROMs the
+    // database doesn't know: Bnnn adds VX, 8xy1/8xy2/8xy3 leave VF alone and sprites wrap around.
+    pub fn new() -> Self {
+        Self {
+            vf_reset: false,
+            memory: true,
+            shift: false,
+            jump: true,
+            display_wait: false,
+            clip: false,
+        }
+    }
+
+    pub fn from_preset(name: &str) -> Option<Self> {
+        match name {
+            // The original COSMAC VIP interpreter.
+            "chip8" => Some(Self {
+                vf_reset: true,
+                memory: true,
+                shift: false,
+                jump: false,
+                display_wait: true,
+                clip: true,
+            }),
+            "schip" => Some(Self {
+                vf_reset: false,
+                memory: false,
+                shift: true,
+                jump: true,
+                display_wait: false,
+                clip: true,
+            }),
+            "xochip" => Some(Self {
+                vf_reset: false,
+                memory: true,
+                shift: false,
+                jump: false,
+                display_wait: false,
+                clip: false,
+            }),
+            _ => None,
+        }
+    }
+
+    // None for unknown names.
+    pub fn get(&self, name: &str) -> Option<bool> {
+        match name {
+            "vf-reset" => Some(self.vf_reset),
+            "memory" => Some(self.memory),
+            "shift" => Some(self.shift),
+            "jump" => Some(self.jump),
+            "display-wait" => Some(self.display_wait),
+            "clip" => Some(self.clip),
+            _ => None,
+        }
+    }
+
+    // Returns false for unknown names.
+    pub fn set(&mut self, name: &str, enabled: bool) -> bool {
+        match name {
+            "vf-reset" => self.vf_reset = enabled,
+            "memory" => self.memory = enabled,
+            "shift" => self.shift = enabled,
+            "jump" => self.jump = enabled,
+            "display-wait" => self.display_wait = enabled,
+            "clip" => self.clip = enabled,
+            _ => return false,
+        }
+        return true;
+    }
+}
@@ -0,0 +1,109 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use rand::Rng;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+use super::memory::Memory;
+
+// Source of the random bytes used by Cxnn. The machine owns one, so runs are reproducible from a seed.
+pub trait RandomSource {
+    fn next(&mut self, memory: &mut Memory) -> u8;
+
+    // Called once per 60Hz tick, for generators that depend on timing like the VIP's.
+    fn tick(&mut self) {}
+
+    // A copy in the same state, which draws the same numbers from here on.
+    fn clone_box(&self) -> Box<dyn RandomSource>;
+}
+
+#[derive(Clone)]
+pub struct DefaultRandom {
+    rng: StdRng,
+}
+
+impl DefaultRandom {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl RandomSource for DefaultRandom {
+    fn next(&mut self, _memory: &mut Memory) -> u8 {
+        return self.rng.gen::<u8>();
+    }
+
+    fn clone_box(&self) -> Box<dyn RandomSource> {
+        return Box::new(self.clone());
+    }
+}
+
+// An approximation of the COSMAC VIP interpreter's routine, NOT a bit exact copy of it: a counter
+// advanced by the 60Hz interrupt and on every call indexes into the interpreter's page at 0x100, and
+// the byte found there is accumulated into the result. The VIP interpreter lived in RAM at
+// 0x000-0x1FF, so this reads the emulated interpreter region, which doesn't hold the VIP's code. The
+// counter is accumulated as well, otherwise an empty region would always produce the same number.
+// The sequence has the same character (short, predictable, timing dependent) but different values.
+#[derive(Clone)]
+pub struct VipLikeRandom {
+    counter: u8,
+    accumulator: u8,
+}
+
+impl VipLikeRandom {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            counter: seed as u8,
+            accumulator: (seed >> 8) as u8,
+        }
+    }
+}
+
+impl RandomSource for VipLikeRandom {
+    fn next(&mut self, memory: &mut Memory) -> u8 {
+        self.counter = self.counter.wrapping_add(1);
+        let byte = memory.get_ram()[0x100 + self.counter as usize];
+        self.accumulator = self.accumulator.wrapping_add(byte).wrapping_add(self.counter);
+        return self.accumulator;
+    }
+
+    fn tick(&mut self) {
+        self.counter = self.counter.wrapping_add(1);
+    }
+
+    fn clone_box(&self) -> Box<dyn RandomSource> {
+        return Box::new(self.clone());
+    }
+}
+
+pub const GENERATORS: [&str; 2] = ["default", "vip-like"];
+
+pub fn from_name(name: &str, seed: u64) -> Option<Box<dyn RandomSource>> {
+    match name {
+        "default" => Some(Box::new(DefaultRandom::new(seed))),
+        "vip-like" => Some(Box::new(VipLikeRandom::new(seed))),
+        _ => None,
+    }
+}
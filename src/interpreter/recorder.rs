@@ -0,0 +1,125 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Write;
+
+use super::cpu::HEIGHT;
+use super::cpu::WIDTH;
+use super::display::Palette;
+
+// GIF delays are in centiseconds and viewers show frames of 1cs or less for 10cs, so 60Hz can't be
+// played back. GIFs run at 50 frames per second (2cs each) instead: one frame out of every 6 is dropped.
+const GIF_DELAY: u16 = 2;
+const GIF_DROP_EVERY: u64 = 6;
+
+enum Sink {
+    Gif(gif::Encoder<BufWriter<File>>),
+    Raw(Box<dyn Write>),
+}
+
+// Captures one frame per 60Hz tick, either as an animated GIF at native resolution (at 50 frames
+// per second, see GIF_DELAY) or as raw RGB24 frames (64x32, row-major) that can be piped into an
+// external encoder.
+pub struct Recorder {
+    sink: Sink,
+    path: String,
+    palette: Palette,
+    frames: u64,
+}
+
+impl Recorder {
+    // "-" writes raw frames to stdout, a path ending in ".gif" writes a GIF, anything else raw frames.
+    pub fn new(path: &str, palette: Palette) -> Result<Self, String> {
+        let sink = if path == "-" {
+            Sink::Raw(Box::new(BufWriter::new(std::io::stdout())))
+        } else if path.to_lowercase().ends_with(".gif") {
+            let file = File::create(path).map_err(|error| format!("can't create {}: {}", path, error))?;
+            let mut global_palette = Vec::<u8>::new();
+            for color in palette.iter() {
+                global_palette.extend_from_slice(color);
+            }
+            let mut encoder = gif::Encoder::new(BufWriter::new(file), WIDTH as u16, HEIGHT as u16, &global_palette)
+                .map_err(|error| format!("can't write the GIF header to {}: {}", path, error))?;
+            encoder.set_repeat(gif::Repeat::Infinite).map_err(|error| format!("can't write the GIF header to {}: {}", path, error))?;
+            Sink::Gif(encoder)
+        } else {
+            let file = File::create(path).map_err(|error| format!("can't create {}: {}", path, error))?;
+            Sink::Raw(Box::new(BufWriter::new(file)))
+        };
+
+        return Ok(Self {
+            sink,
+            path: path.to_string(),
+            palette,
+            frames: 0,
+        });
+    }
+
+    pub fn capture(&mut self, pixels: &[[bool; WIDTH]; HEIGHT]) {
+        match &mut self.sink {
+            Sink::Gif(_) if self.frames % GIF_DROP_EVERY == GIF_DROP_EVERY - 1 => {},
+            Sink::Gif(encoder) => {
+                let mut indices = Vec::<u8>::with_capacity(WIDTH * HEIGHT);
+                for row in pixels.iter() {
+                    for pixel in row.iter() {
+                        indices.push(*pixel as u8);
+                    }
+                }
+                let frame = gif::Frame {
+                    width: WIDTH as u16,
+                    height: HEIGHT as u16,
+                    delay: GIF_DELAY,
+                    buffer: Cow::Owned(indices),
+                    ..Default::default()
+                };
+                encoder.write_frame(&frame).expect("Failed to write GIF frame.");
+            },
+            Sink::Raw(writer) => {
+                let mut rgb = Vec::<u8>::with_capacity(WIDTH * HEIGHT * 3);
+                for row in pixels.iter() {
+                    for pixel in row.iter() {
+                        rgb.extend_from_slice(&self.palette[*pixel as usize]);
+                    }
+                }
+                writer.write_all(&rgb).expect("Failed to write raw frame.");
+            }
+        }
+        self.frames += 1;
+    }
+
+    // Flushes everything and writes the GIF trailer, the file isn't valid before this is called.
+    pub fn finish(self) {
+        match self.sink {
+            Sink::Gif(encoder) => {
+                let mut writer = encoder.into_inner().expect("Failed to finalize GIF.");
+                writer.flush().expect("Failed to finalize GIF.");
+            },
+            Sink::Raw(mut writer) => {
+                writer.flush().expect("Failed to flush raw frames.");
+            }
+        }
+        eprintln!("Recorded {} frames to {}.", self.frames, self.path);
+    }
+}
@@ -0,0 +1,217 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::fmt;
+use std::fs;
+use std::io::Cursor;
+use std::io::Read;
+use std::path::Path;
+
+use serde::Deserialize;
+
+// Extensions picked out of zip archives, in case they contain more than the ROM (e.g. a readme).
+const ROM_EXTENSIONS: [&str; 8] = ["ch8", "c8", "sc8", "xo8", "rom", "bin", "hex", "txt"];
+// Files read as hex dumps, everything else is a binary unless hex is forced.
+const HEX_EXTENSIONS: [&str; 2] = ["hex", "txt"];
+
+#[derive(Debug)]
+pub enum RomError {
+    Io(std::io::Error),
+    Empty,
+    TooLarge { size: usize, limit: usize },
+    Hex { line: usize, token: String },
+    Zip(String),
+    Cartridge(String),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomError::Io(error) => write!(f, "{}", error),
+            RomError::Empty => write!(f, "the ROM is empty"),
+            RomError::TooLarge { size, limit } => write!(f, "the ROM is {} bytes, at most {} bytes fit in memory", size, limit),
+            RomError::Hex { line, token } => write!(f, "invalid hex \"{}\" on line {}", token, line),
+            RomError::Zip(error) => write!(f, "invalid zip archive, {}", error),
+            RomError::Cartridge(error) => write!(f, "invalid Octo cartridge, {}", error),
+        }
+    }
+}
+
+impl From<std::io::Error> for RomError {
+    fn from(error: std::io::Error) -> Self {
+        RomError::Io(error)
+    }
+}
+
+// Octo cartridges carry the program's source, not a binary.
+#[derive(Deserialize)]
+struct Cartridge {
+    program: String,
+}
+
+// Reads ROMs from raw binaries, hex text dumps ("00E0 A22A ...", "0x00, 0xE0, ...") and zip archives,
+// "-" reads from stdin. Octo cartridge GIFs are recognized so they can be rejected with a useful error,
+// they carry Octo source code and only the rare ones made of byte literals load. Cartridges and zip
+// archives are recognized by their content, hex dumps by their extension (.hex, .txt) since a binary
+// can happen to be all printable.
+#[derive(Clone, Copy)]
+pub struct RomLoader {
+    // Read everything that isn't a cartridge or zip archive as a hex dump, whatever its name.
+    hex: bool,
+}
+
+impl Default for RomLoader {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+impl RomLoader {
+    pub fn new() -> Self {
+        RomLoader { hex: false }
+    }
+
+    pub fn set_hex(&mut self, hex: bool) {
+        self.hex = hex;
+    }
+
+    pub fn load(&self, path: &str) -> Result<Vec<u8>, RomError> {
+        let data = if path == "-" {
+            let mut buffer = Vec::<u8>::new();
+            std::io::stdin().read_to_end(&mut buffer)?;
+            buffer
+        } else {
+            fs::read(path)?
+        };
+
+        return self.decode(path, data);
+    }
+
+    // The ROM in `data`, read from a file called `name`.
+    pub fn decode(&self, name: &str, data: Vec<u8>) -> Result<Vec<u8>, RomError> {
+        let rom = if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            self.decode_cartridge(&data)?
+        } else if data.starts_with(b"PK\x03\x04") || data.starts_with(b"PK\x05\x06") {
+            // A file header, or the end of the central directory of an empty archive.
+            self.decode_zip(name, data)?
+        } else if self.hex || has_extension(name, &HEX_EXTENSIONS) {
+            parse_hex(&String::from_utf8_lossy(&data))?
+        } else {
+            data
+        };
+        if rom.is_empty() {
+            return Err(RomError::Empty);
+        }
+        return Ok(rom);
+    }
+
+    fn decode_zip(&self, name: &str, data: Vec<u8>) -> Result<Vec<u8>, RomError> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(data)).map_err(|error| RomError::Zip(error.to_string()))?;
+
+        // The only file in the archive, or else the first one that looks like a ROM.
+        let files: Vec<String> = archive.file_names()
+            .filter(|file| !file.ends_with('/'))
+            .map(|file| file.to_string())
+            .collect();
+        let file = if files.len() == 1 {
+            files[0].clone()
+        } else {
+            let mut candidates: Vec<&String> = files.iter()
+                .filter(|file| has_extension(file, &ROM_EXTENSIONS))
+                .collect();
+            candidates.sort();
+            match candidates.first() {
+                Some(file) => file.to_string(),
+                None => return Err(RomError::Zip(format!("no ROM found in {}", name))),
+            }
+        };
+
+        let mut entry = archive.by_name(&file).map_err(|error| RomError::Zip(error.to_string()))?;
+        let mut buffer = Vec::<u8>::new();
+        entry.read_to_end(&mut buffer)?;
+        eprintln!("Loading {} from {}.", file, name);
+        return self.decode(&file, buffer);
+    }
+
+    // Octo packs a length-prefixed JSON payload ({"program": ..., "options": ...}) into the two low bits
+    // of the cartridge image's palette indices, four pixels per byte, most significant bits first.
+    // Source: https://github.com/JohnEarnest/Octo/blob/gh-pages/js/sharing.js
+    fn decode_cartridge(&self, data: &[u8]) -> Result<Vec<u8>, RomError> {
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(data).map_err(|error| RomError::Cartridge(error.to_string()))?;
+        let frame = decoder.read_next_frame()
+            .map_err(|error| RomError::Cartridge(error.to_string()))?
+            .ok_or(RomError::Cartridge("the image has no frames".to_string()))?;
+
+        let bytes: Vec<u8> = frame.buffer.chunks_exact(4)
+            .map(|pixels| pixels.iter().fold(0, |byte, pixel| (byte << 2) | (pixel & 3)))
+            .collect();
+        if bytes.len() < 4 {
+            return Err(RomError::Cartridge("the image is too small".to_string()));
+        }
+        let size = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+        let payload = bytes.get(4..4 + size).ok_or(RomError::Cartridge("the payload is truncated".to_string()))?;
+        let cartridge: Cartridge = serde_json::from_slice(payload).map_err(|error| RomError::Cartridge(error.to_string()))?;
+
+        // There's no Octo assembler here, only programs made of plain byte literals can be loaded.
+        let source: String = cartridge.program.lines()
+            .map(|line| line.split('#').next().unwrap())
+            .collect::<Vec<&str>>()
+            .join("\n");
+        return parse_hex(&source).map_err(|_| RomError::Cartridge(
+            "its program is Octo source code, only cartridges made of byte literals can be run (there's no Octo assembler), export the ROM from Octo instead".to_string()));
+    }
+}
+
+fn has_extension(name: &str, extensions: &[&str]) -> bool {
+    let extension = Path::new(name).extension().map(|extension| extension.to_string_lossy().to_lowercase());
+    return extension.is_some_and(|extension| extensions.contains(&extension.as_str()));
+}
+
+// Bytes are separated by whitespace or commas and may be prefixed with "0x" or "$", words ("00E0") are
+// split into bytes. Tokens ending in ':' are addresses (e.g. "0200:") and ignored, as are comments
+// starting with '#', ';' or "//".
+fn parse_hex(text: &str) -> Result<Vec<u8>, RomError> {
+    let mut rom = Vec::<u8>::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split(['#', ';']).next().unwrap();
+        let line = line.split("//").next().unwrap();
+        for token in line.split(|c: char| c.is_whitespace() || c == ',').filter(|token| !token.is_empty()) {
+            if token.ends_with(':') {
+                continue;
+            }
+            let digits = token.strip_prefix("0x")
+                .or(token.strip_prefix("0X"))
+                .or(token.strip_prefix('$'))
+                .unwrap_or(token);
+            let invalid = || RomError::Hex { line: number + 1, token: token.to_string() };
+            if digits.is_empty() || digits.len() % 2 != 0 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(invalid());
+            }
+            for i in (0..digits.len()).step_by(2) {
+                rom.push(u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| invalid())?);
+            }
+        }
+    }
+    return Ok(rom);
+}
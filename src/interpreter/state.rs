@@ -0,0 +1,113 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::fs;
+
+use super::cpu::CpuState;
+use super::cpu::CPU;
+use super::cpu::HEIGHT;
+use super::cpu::WIDTH;
+use super::memory::Memory;
+
+const MAGIC: &[u8; 4] = b"C8ST";
+const VERSION: u8 = 2;
+const HEADER_SIZE: usize = 4 + 1 + 2 * 4 + 16 + 2 + WIDTH * HEIGHT;
+
+// Save states hold the CPU registers, timers, framebuffer and RAM, little endian:
+// magic, version, pc, i, sp, stack size, stack, v[16], delay timer, sound timer, framebuffer (one byte per pixel), ram (as large as the memory map, 4K for most platforms).
+// The random source isn't included, so Cxnn results can differ after loading.
+pub fn save(path: &str, cpu: &CPU, memory: &Memory) {
+    let state = cpu.get_state();
+
+    let mut data = Vec::<u8>::with_capacity(HEADER_SIZE + 2 * state.stack.len() + memory.get_ram().len());
+    data.extend_from_slice(MAGIC);
+    data.push(VERSION);
+    data.extend_from_slice(&state.pc.to_le_bytes());
+    data.extend_from_slice(&state.i.to_le_bytes());
+    data.extend_from_slice(&state.sp.to_le_bytes());
+    data.extend_from_slice(&(state.stack.len() as u16).to_le_bytes());
+    for address in state.stack.iter() {
+        data.extend_from_slice(&address.to_le_bytes());
+    }
+    data.extend_from_slice(&state.v);
+    data.push(state.delay_timer);
+    data.push(state.sound_timer);
+    for row in state.buffer.iter() {
+        for pixel in row.iter() {
+            data.push(*pixel as u8);
+        }
+    }
+    data.extend_from_slice(memory.get_ram());
+
+    fs::write(path, data).expect("Failed to write save state.");
+    eprintln!("Saved state to {}.", path);
+}
+
+// Nothing is changed if the state can't be loaded.
+pub fn load(path: &str, cpu: &mut CPU, memory: &mut Memory) -> Result<(), String> {
+    let data = fs::read(path).map_err(|error| format!("failed to read save state {}: {}", path, error))?;
+    let incompatible = || format!("{} is not a compatible save state", path);
+    if data.len() < HEADER_SIZE || &data[0..4] != MAGIC || data[4] != VERSION {
+        return Err(incompatible());
+    }
+
+    let mut offset = 5;
+    let mut state = CpuState {
+        buffer: [[false; WIDTH]; HEIGHT],
+        pc: read_u16(&data, &mut offset),
+        i: read_u16(&data, &mut offset),
+        sp: read_u16(&data, &mut offset),
+        stack: Vec::new(),
+        v: [0; 16],
+        delay_timer: 0,
+        sound_timer: 0,
+    };
+    let stack_size = read_u16(&data, &mut offset) as usize;
+    if data.len() != HEADER_SIZE + 2 * stack_size + memory.get_ram().len() {
+        return Err(incompatible());
+    }
+    for _ in 0..stack_size {
+        state.stack.push(read_u16(&data, &mut offset));
+    }
+    state.v.copy_from_slice(&data[offset..offset + 16]);
+    offset += 16;
+    state.delay_timer = data[offset];
+    state.sound_timer = data[offset + 1];
+    offset += 2;
+    for row in state.buffer.iter_mut() {
+        for pixel in row.iter_mut() {
+            *pixel = data[offset] != 0;
+            offset += 1;
+        }
+    }
+
+    cpu.set_state(&state).map_err(|error| format!("{} can't be loaded, {}", path, error))?;
+    memory.set_ram(&data[offset..]);
+    eprintln!("Loaded state from {}.", path);
+    return Ok(());
+}
+
+fn read_u16(data: &[u8], offset: &mut usize) -> u16 {
+    let value = u16::from_le_bytes([data[*offset], data[*offset + 1]]);
+    *offset += 2;
+    return value;
+}
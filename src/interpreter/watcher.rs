@@ -0,0 +1,137 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::fs;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+
+use super::coverage::Coverage;
+use super::cpu::CPU;
+use super::font::Font;
+use super::memory::Memory;
+use super::rom::RomError;
+use super::rom::RomLoader;
+
+// Watches the ROM file for changes by polling its modification time and size, which is cheap enough
+// to do from the frame loop. Editors and assemblers often write a file in several steps, so a change
+// is only picked up once the file has stayed the same for a whole check interval.
+
+const CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+// What happens to the running program when the ROM changes.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Reload {
+    // Reload the memory and reset the CPU, as if the interpreter was started again.
+    Reset,
+    // Only write the bytes that changed, registers, the stack, timers and the screen are kept.
+    Patch,
+}
+
+pub const RELOADS: [&str; 2] = ["reset", "patch"];
+
+impl Reload {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "reset" => Some(Reload::Reset),
+            "patch" => Some(Reload::Patch),
+            _ => None,
+        }
+    }
+}
+
+pub struct RomWatcher {
+    path: String,
+    loader: RomLoader,
+    // Modification time and size of the loaded file.
+    loaded: Option<(SystemTime, u64)>,
+    // A change seen at the last check, loaded if the file is still the same at the next one.
+    pending: Option<(SystemTime, u64)>,
+    last_check: Instant,
+}
+
+impl RomWatcher {
+    pub fn new(path: &str, loader: RomLoader) -> Self {
+        return RomWatcher {
+            path: path.to_string(),
+            loader,
+            loaded: stamp(path),
+            pending: None,
+            last_check: Instant::now(),
+        };
+    }
+
+    // The new ROM once the file has changed and settled, None otherwise.
+    pub fn poll(&mut self) -> Option<Result<Vec<u8>, RomError>> {
+        if self.last_check.elapsed() < CHECK_INTERVAL {
+            return None;
+        }
+        self.last_check = Instant::now();
+
+        // A missing file is usually being replaced, wait for the new one.
+        let current = stamp(&self.path);
+        if current.is_none() || current == self.loaded {
+            self.pending = None;
+            return None;
+        }
+        if current != self.pending {
+            self.pending = current;
+            return None;
+        }
+        self.loaded = current;
+        self.pending = None;
+        return Some(self.loader.load(&self.path));
+    }
+}
+
+fn stamp(path: &str) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    return Some((metadata.modified().ok()?, metadata.len()));
+}
+
+// Replaces the running program, `previous` is the ROM that was loaded so far. A ROM that doesn't fit
+// leaves the machine as it was.
+pub fn reload(mode: Reload, cpu: &mut CPU, memory: &mut Memory, font: &Font, previous: &[u8], rom: &[u8]) -> Result<(), RomError> {
+    match mode {
+        Reload::Reset => {
+            let mut fresh = Memory::new(memory.get_map());
+            fresh.initialize(font);
+            fresh.load_rom(rom)?;
+            *memory = fresh;
+            cpu.reset();
+            // Coverage starts over with the new ROM.
+            if cpu.take_coverage().is_some() {
+                let map = memory.get_map();
+                cpu.set_coverage(Some(Coverage::new(map.size, map.load_address, rom)));
+            }
+        },
+        Reload::Patch => {
+            let patched = memory.patch_rom(previous, rom)?;
+            eprintln!("Patched {} bytes of the ROM.", patched);
+            if let Some(mut coverage) = cpu.take_coverage() {
+                coverage.set_rom(rom);
+                cpu.set_coverage(Some(coverage));
+            }
+        },
+    }
+    return Ok(());
+}
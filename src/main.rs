@@ -20,87 +20,428 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+use std::path::Path;
 use std::thread;
 use std::time::Duration;
 use std::time::Instant;
+use std::time::SystemTime;
 
 use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::keyboard::Scancode;
 
-mod interpreter;
+mod cli;
 
-const PROCESSOR_CLOCK_SPEED: u64 = 500; // 500 Mhz default, configured via option.
+use rs_8chip_interpret::interpreter;
 
-// TODO refactor
-struct ConsoleArgs {
-    path: String,
-}
+use interpreter::audio::Audio;
+use interpreter::coverage::Coverage;
+use interpreter::cpu::Fault;
+use interpreter::cpu::DEFAULT_STACK_DEPTH;
+use interpreter::display::DEFAULT_PALETTE;
+use interpreter::keypad::Rebinder;
+use interpreter::memory::Memory;
+use interpreter::movie::Movie;
+use interpreter::platform::Platform;
+use interpreter::profiler::Profiler;
+use interpreter::recorder::Recorder;
+use interpreter::rom::RomLoader;
+use interpreter::watcher::RomWatcher;
+
+const FRAME_RATE: u32 = 60; // Timers, input and presentation all run at 60Hz.
+const DEFAULT_IPS: u32 = 9; // (500Hz / 60Hz) == ~9 Cycles
+const DEFAULT_KEYMAP: &str = "keymap.toml";
+const DEFAULT_ROM_OVERRIDES: &str = "rom-overrides.json";
 
 fn main() {
-    
-    let context = sdl2::init().unwrap();
-    let mut events = context.event_pump().unwrap();
 
-    // create CPU instance
-    let mut cpu = interpreter::cpu::CPU::new();
+    let args = cli::parse();
+
+    // Headless runs never touch SDL, so they work without a display (e.g. on CI).
+    let context = if args.headless { None } else { Some(sdl2::init().unwrap()) };
+    let mut events = context.as_ref().map(|context| context.event_pump().unwrap());
+    // Connected controllers show up as ControllerDeviceAdded events, including the ones present at startup.
+    let controllers = context.as_ref().and_then(|context| context.game_controller().ok());
+
     // create Keypad instance
     let mut keypad = interpreter::keypad::Keypad::new();
+
+    let mut database = interpreter::database::RomDatabase::new();
+    // Like a broken keymap, a broken database isn't worth stopping for, the bundled one stays.
+    if let Some(directory) = args.rom_db.as_deref() {
+        if let Err(error) = database.load(directory) {
+            eprintln!("Failed to load ROM database: {}.", error);
+        }
+    }
+    let overrides_path = args.rom_overrides.clone().unwrap_or(DEFAULT_ROM_OVERRIDES.to_string());
+    if args.rom_overrides.is_some() || Path::new(&overrides_path).exists() {
+        if let Err(error) = database.load_overrides(&overrides_path) {
+            eprintln!("Failed to load ROM overrides: {}.", error);
+        }
+    }
+
+    // load rom
+    let mut loader = RomLoader::new();
+    loader.set_hex(args.hex);
+    let mut rom_data = loader.load(&args.rom).unwrap_or_else(|error| {
+        eprintln!("Failed to load ROM from {}: {}", args.rom, error);
+        std::process::exit(1);
+    });
+    let rom = database.identify(&rom_data);
+    let platform = args.platform
+        .or(rom.as_ref().map(|rom| rom.platform))
+        .unwrap_or(Platform::OriginalChip8);
+
+    // The platform decides where things live in memory, unless overridden.
+    let mut map = platform.memory_map();
+    map.load_address = args.load_address.unwrap_or(map.load_address);
+    map.font_address = args.font_address.unwrap_or(map.font_address);
+    map.reserved = args.reserved.unwrap_or(map.reserved);
+    map.bounds = args.bounds.unwrap_or(map.bounds);
+    let font = args.font.clone()
+        .or(args.platform.map(|platform| platform.font()))
+        .or(rom.as_ref().map(|rom| rom.platform.font()))
+        .unwrap_or_default();
+    if map.load_address as usize >= map.size || map.font_address as usize + font.size() > map.size {
+        eprintln!("Invalid memory map, the load and font addresses must be within the {} bytes of RAM.", map.size);
+        std::process::exit(1);
+    }
+
     // create Memory instance
-    let mut memory = interpreter::memory::Memory::new();
+    let mut memory = Memory::new(map);
+    // copy font to memory
+    memory.initialize(&font);
+    if let Err(error) = memory.load_rom(&rom_data) {
+        eprintln!("Failed to load ROM from {}: {}", args.rom, error);
+        std::process::exit(1);
+    }
+    // create CPU instance
+    let mut cpu = interpreter::cpu::CPU::new(&map);
+
+    // Settings given on the command line (or config file) win over the ROM database, which wins over the defaults.
+    let mut quirks = args.quirks
+        .or(args.platform.map(|platform| platform.quirks()))
+        .or(rom.as_ref().map(|rom| rom.quirks))
+        .unwrap_or_default();
+    for (name, enabled) in args.quirk.iter() {
+        quirks.set(name, *enabled);
+    }
+    let ips = args.ips
+        .or(args.platform.map(|platform| platform.default_tickrate()))
+        .or(rom.as_ref().map(|rom| rom.tickrate))
+        .unwrap_or(DEFAULT_IPS);
+    let palette = args.palette
+        .or(rom.as_ref().and_then(|rom| rom.palette))
+        .unwrap_or(DEFAULT_PALETTE);
+    if let Some(rom) = rom.as_ref() {
+        keypad.apply_key_hints(&rom.keys);
+    }
+
     // create Display instance
-    let mut display = interpreter::display::Display::new(&context);
+    let mut display = context.as_ref().map(|context| interpreter::display::Display::new(context, args.scale, palette));
+    // create Audio instance
+    let mut audio = match context.as_ref() {
+        Some(context) => Audio::new(context, args.tone),
+        None => Audio::new_null(args.tone),
+    };
+    if let Some(path) = args.wav.as_deref() {
+        audio.export_wav(path);
+    }
 
-    // copy font to memory
-    memory.initialize();
+    cpu.set_quirks(quirks);
+    let stack_depth = args.stack_depth
+        .or(args.platform.map(|platform| platform.stack_depth()))
+        .or(rom.as_ref().map(|rom| rom.platform.stack_depth()))
+        .unwrap_or(Some(DEFAULT_STACK_DEPTH));
+    cpu.set_stack(stack_depth, args.stack_in_ram);
+    cpu.set_backend(args.backend);
+    cpu.set_trace(args.trace);
+    if args.profile.is_some() || args.profile_stacks.is_some() {
+        cpu.set_profiler(Some(Profiler::new(map.size)));
+    }
+    if args.coverage.is_some() {
+        cpu.set_coverage(Some(Coverage::new(map.size, map.load_address, &rom_data)));
+    }
 
-    let path = std::env::args().nth(1).expect("No ROM file found.");
-    // load rom
-    memory.load_rom(&path);
+    // Save states are kept next to the ROM, F5 saves and F9 loads. A ROM read from stdin has no place for them.
+    let state_path = (args.rom != "-").then(|| format!("{}.state", args.rom));
+    if let Some(path) = args.load_state.as_deref() {
+        if let Err(error) = interpreter::state::load(path, &mut cpu, &mut memory) {
+            eprintln!("Failed to load state: {}.", error);
+            std::process::exit(1);
+        }
+    }
+
+    // Key bindings, per-ROM overrides are looked up by the ROM's file name.
+    let rom_name = Path::new(&args.rom).file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or(args.rom.clone());
+    let keymap_path = args.keymap.clone().unwrap_or(DEFAULT_KEYMAP.to_string());
+    if !args.headless && (args.keymap.is_some() || Path::new(&keymap_path).exists()) {
+        // A broken keymap isn't worth stopping for, the default bindings stay.
+        if let Err(error) = keypad.load_keymap(&keymap_path, &rom_name) {
+            eprintln!("Failed to load keymap: {}.", error);
+        }
+    }
+
+    // Replays take the seed and all inputs from the movie, the keyboard and controllers are ignored.
+    // A replay only stays in sync with the platform, speed and quirks it was recorded with.
+    let replay = args.replay.as_deref().map(|path| {
+        let replay = Movie::load(path).unwrap_or_else(|error| {
+            eprintln!("Failed to load movie: {}.", error);
+            std::process::exit(1);
+        });
+        if let Err(error) = replay.check(platform, ips, quirks) {
+            eprintln!("Can't replay {}, {}.", path, error);
+            std::process::exit(1);
+        }
+        replay
+    });
+    let (rng, seed) = match replay.as_ref() {
+        Some(replay) => (replay.rng.clone(), replay.seed),
+        None => (args.rng.clone(), args.seed.unwrap_or_else(rand::random)),
+    };
+    let random_source = interpreter::random::from_name(&rng, seed).unwrap_or_else(|| {
+        eprintln!("Failed to load movie: unknown random generator \"{}\".", rng);
+        std::process::exit(1);
+    });
+    cpu.set_random_source(random_source);
+    let mut movie = args.record_movie.as_ref().map(|_| Movie::new(&rng, seed, platform, ips, quirks));
+
+    if let Some(limit) = args.bench {
+        match interpreter::bench::run(&mut cpu, &mut memory, &mut keypad, ips, limit) {
+            Ok(report) => print!("{}", report),
+            Err(error) => {
+                eprintln!("CPU fault: {}.", error);
+                std::process::exit(1);
+            },
+        }
+        return;
+    }
+
+    let mut recorder = args.record.as_deref().map(|path| Recorder::new(path, palette).unwrap_or_else(|error| {
+        eprintln!("Failed to start recording: {}.", error);
+        std::process::exit(1);
+    }));
+    let mut watcher = args.watch.map(|_| RomWatcher::new(&args.rom, loader));
+    let mut rebinder: Option<Rebinder> = None;
 
-    // NOTE: this is not a proper way to emulate the processor speed and 500 MHz is arbitrary value.
-    // This part of the code should be completely rewritten while counting the time taken for each instruction
-    // And then properly adjusting the speed, but that's for another day.
+    // NOTE: this is still not a proper way to emulate the processor speed, a fixed amount of
+    // instructions is executed per frame without counting the time taken for each instruction.
     // Source: https://jackson-s.me/2019/07/13/Chip-8-Instruction-Scheduling-and-Frequency.html
-    let interval = Duration::from_secs_f32(1.0 / PROCESSOR_CLOCK_SPEED as f32);
+    let interval = Duration::from_secs_f32(1.0 / FRAME_RATE as f32);
+
+    let mut frame: u64 = 0;
+    // A fault halts the CPU, headless runs stop right away and exit with an error.
+    let mut fault: Option<Fault> = None;
 
-    let mut division_cycles: u32 = 0;
-    
     'running: loop {
 
         let start_time = Instant::now();
-        
-        cpu.execute(&mut display, &mut memory, &mut keypad);
-        division_cycles += 1;
-        
-        let elapsed = Instant::now() - start_time;
-        if elapsed < interval {
-            thread::sleep(interval - elapsed);
+
+        if let Some(events) = events.as_mut() {
+            for event in events.poll_iter() {
+                match event {
+                    Event::Quit { .. } => break 'running,
+                    Event::KeyDown { scancode: Some(scancode), repeat: false, .. } if rebinder.is_some() => {
+                        let screen = rebinder.as_mut().unwrap();
+                        let done = match scancode {
+                            Scancode::Escape => true,
+                            Scancode::Tab => screen.skip(),
+                            _ => screen.bind(&mut keypad, scancode),
+                        };
+                        if done {
+                            rebinder = None;
+                            if let Err(error) = keypad.save_keymap(&keymap_path, &rom_name) {
+                                eprintln!("Failed to save keymap: {}.", error);
+                            }
+                            display.as_mut().unwrap().set_title(interpreter::display::TITLE);
+                        }
+                    },
+                    Event::ControllerDeviceAdded { which, .. } => {
+                        match controllers.as_ref().unwrap().open(which) {
+                            Ok(controller) => keypad.add_controller(controller),
+                            Err(error) => eprintln!("Failed to open controller {}: {}", which, error),
+                        }
+                    },
+                    Event::ControllerDeviceRemoved { which, .. } => {
+                        keypad.remove_controller(which);
+                    },
+                    Event::KeyDown { keycode: Some(Keycode::F1), repeat: false, .. } => {
+                        // Open the rebinding screen, emulation is paused until it's closed.
+                        rebinder = Some(Rebinder::new());
+                    },
+                    Event::KeyDown { keycode: Some(Keycode::F12), repeat: false, .. } => {
+                        // Toggle recording
+                        if let Some(recorder) = recorder.take() {
+                            recorder.finish();
+                        } else {
+                            let timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+                            recorder = match Recorder::new(&format!("capture-{}.gif", timestamp), palette) {
+                                Ok(recorder) => Some(recorder),
+                                Err(error) => {
+                                    eprintln!("Failed to start recording: {}.", error);
+                                    None
+                                },
+                            };
+                        }
+                    },
+                    Event::KeyDown { keycode: Some(Keycode::F8), repeat: false, .. } => {
+                        audio.toggle_mute();
+                    },
+                    Event::KeyDown { keycode: Some(Keycode::F5 | Keycode::F9), repeat: false, .. } if state_path.is_none() => {
+                        eprintln!("Save states are kept next to the ROM file, a ROM read from stdin can't have any.");
+                    },
+                    Event::KeyDown { keycode: Some(Keycode::F5), repeat: false, .. } => {
+                        interpreter::state::save(state_path.as_deref().unwrap(), &cpu, &memory);
+                    },
+                    Event::KeyDown { keycode: Some(Keycode::F9), repeat: false, .. } if Path::new(state_path.as_deref().unwrap()).exists() => {
+                        if let Err(error) = interpreter::state::load(state_path.as_deref().unwrap(), &mut cpu, &mut memory) {
+                            eprintln!("Failed to load state: {}.", error);
+                        }
+                    },
+                    _ => {}
+                }
+            }
+
+            // Check for new inputs
+            keypad.is_key_down(events);
         }
 
-        for event in events.poll_iter() {
-            if let Event::Quit { .. } = event {
-                break 'running;
-            };
+        // The ROM changed on disk, a failed reload keeps the running program.
+        if let Some(result) = watcher.as_mut().and_then(|watcher| watcher.poll()) {
+            let reloaded = result.and_then(|new_rom| {
+                interpreter::watcher::reload(args.watch.unwrap(), &mut cpu, &mut memory, &font, &rom_data, &new_rom)?;
+                rom_data = new_rom;
+                return Ok(());
+            });
+            match reloaded {
+                Ok(()) => {
+                    // A halted program gets another chance with the new code.
+                    fault = None;
+                    if let Some(display) = display.as_mut() {
+                        display.set_title(interpreter::display::TITLE);
+                    }
+                },
+                Err(error) => eprintln!("Failed to reload ROM from {}: {}", args.rom, error),
+            }
         }
-    
-        // Check for new inputs
-        keypad.is_key_down(&events);
 
-        // (500Hz / 60Hz) == ~9 Cycles
-        if division_cycles == 9
-        {
+        if let Some(rebinder) = rebinder.as_ref() {
+            let display = display.as_mut().unwrap();
+            display.set_title(&rebinder.prompt(&keypad));
+            display.draw(&rebinder.render());
+        } else if fault.is_some() {
+            // Halted, keep showing the last frame.
+            if let Some(display) = display.as_mut() {
+                display.draw(cpu.get_buffer());
+            }
+        } else {
+            // Input is sampled once per frame.
+            if let Some(replay) = replay.as_ref() {
+                match replay.inputs.get(frame as usize) {
+                    Some(state) => keypad.set_state(*state),
+                    None => break 'running,
+                }
+            }
+            if let Some(movie) = movie.as_mut() {
+                movie.inputs.push(keypad.get_state());
+            }
+
+            if let Err(error) = cpu.run(&mut memory, &mut keypad, ips) {
+                eprintln!("CPU fault: {}.", error);
+                fault = Some(error);
+                match display.as_mut() {
+                    Some(display) => display.set_title(&format!("{} - halted, {}", interpreter::display::TITLE, error)),
+                    None => break 'running,
+                }
+            }
+
             if cpu.get_delay_timer() > 0
             {
                 cpu.dec_delay_timer();
             }
+            cpu.tick_random_source();
+            cpu.vblank();
+
+            // The tone plays for every frame the sound timer is non-zero.
+            audio.update(cpu.get_sound_timer() > 0);
 
             if cpu.get_sound_timer() > 0
             {
-                // NOTE: Will only work on *NIX
-                // std::process::Command::new("echo \"\x07\"").exec();
                 cpu.dec_sound_timer();
             }
 
-            division_cycles = 0; // reset cycle count.
+            if let Some(display) = display.as_mut() {
+                display.draw(cpu.get_buffer());
+            }
+
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.capture(cpu.get_buffer());
+            }
+
+            frame += 1;
+            if args.frames.is_some_and(|frames| frame >= frames) {
+                break 'running;
+            }
+        }
+
+        // Headless runs go as fast as possible.
+        if display.is_some() {
+            let elapsed = Instant::now() - start_time;
+            if elapsed < interval {
+                thread::sleep(interval - elapsed);
+            }
+        }
+    }
+
+    if let Some(recorder) = recorder.take() {
+        recorder.finish();
+    }
+    audio.finish();
+
+    if let Some(coverage) = cpu.take_coverage() {
+        let path = args.coverage.as_deref().unwrap();
+        match coverage.save(path) {
+            Ok(()) => eprintln!("Coverage written to {}.", path),
+            Err(error) => eprintln!("Failed to write coverage to {}: {}", path, error),
+        }
+    }
+    if let Some(profiler) = cpu.take_profiler() {
+        if let Some(path) = args.profile.as_deref() {
+            match profiler.save_report(path) {
+                Ok(()) => eprintln!("Profile written to {}.", path),
+                Err(error) => eprintln!("Failed to write profile to {}: {}", path, error),
+            }
+        }
+        if let Some(path) = args.profile_stacks.as_deref() {
+            match profiler.save_collapsed_stacks(path) {
+                Ok(()) => eprintln!("Call stacks written to {}.", path),
+                Err(error) => eprintln!("Failed to write call stacks to {}: {}", path, error),
+            }
+        }
+    }
+
+    if memory.get_wrap_count() > 0 {
+        eprintln!("{} memory accesses went past the end of RAM and wrapped around.", memory.get_wrap_count());
+    }
+
+    let hash = interpreter::movie::framebuffer_hash(cpu.get_buffer());
+    if let Some(mut movie) = movie.take() {
+        movie.hash = Some(hash);
+        movie.save(args.record_movie.as_deref().unwrap());
+    }
+
+    if fault.is_some() {
+        std::process::exit(1);
+    }
+
+    if let Some(expected) = replay.as_ref().and_then(|replay| replay.hash) {
+        if hash == expected {
+            eprintln!("Replay verified, framebuffer hash {:016x}.", hash);
+        } else {
+            eprintln!("Replay mismatch, framebuffer hash {:016x} (expected {:016x}).", hash, expected);
+            std::process::exit(1);
         }
     }
 }
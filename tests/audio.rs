@@ -0,0 +1,82 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// The tone plays for exactly the frames the sound timer is non-zero, checked on the exported WAV.
+
+mod common;
+
+use rs_8chip_interpret::interpreter::audio::Audio;
+use rs_8chip_interpret::interpreter::audio::Tone;
+use rs_8chip_interpret::interpreter::keypad::Keypad;
+
+const ROM: [u8; 20] = [
+    0x60, 0x04, // v0 := 4
+    0xF0, 0x18, // buzzer := v0
+    0x6A, 0x0A, // va := 10
+    0xFA, 0x15, // delay := va
+    0xFB, 0x07, // vb := delay
+    0x3B, 0x00, // if vb != 0 then
+    0x12, 0x08, // jump 0x208
+    0x60, 0x02, // v0 := 2
+    0xF0, 0x18, // buzzer := v0
+    0x12, 0x12, // jump 0x212
+];
+
+const FRAMES: usize = 16;
+// 44100 / 60 samples.
+const FRAME_SAMPLES: usize = 735;
+
+#[test]
+fn sound_timer_opens_and_closes_the_tone() {
+    let (mut cpu, mut memory) = common::machine(&ROM);
+    let mut keypad = Keypad::new();
+
+    let path = std::env::temp_dir().join(format!("rs-8chip-audio-{}.wav", std::process::id()));
+    let mut audio = Audio::new_null(Tone::new());
+    audio.export_wav(path.to_str().unwrap());
+    // Same order as the main loop: run the frame, tick the delay timer, play the frame, tick the sound timer.
+    for _ in 0..FRAMES {
+        cpu.run(&mut memory, &mut keypad, 10).unwrap();
+        if cpu.get_delay_timer() > 0 {
+            cpu.dec_delay_timer();
+        }
+        audio.update(cpu.get_sound_timer() > 0);
+        if cpu.get_sound_timer() > 0 {
+            cpu.dec_sound_timer();
+        }
+    }
+    audio.finish();
+
+    let samples: Vec<i16> = hound::WavReader::open(&path).unwrap().samples::<i16>().map(|sample| sample.unwrap()).collect();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(samples.len(), FRAMES * FRAME_SAMPLES);
+    // The first beep lasts 4 frames, the second one starts once the delay timer ran out on frame 10.
+    let audible: Vec<bool> = samples.chunks(FRAME_SAMPLES).map(|frame| frame.iter().any(|sample| *sample != 0)).collect();
+    let expected: Vec<bool> = (0..FRAMES).map(|frame| frame < 4 || frame == 10 || frame == 11).collect();
+    assert_eq!(audible, expected);
+    // Frames with the tone on have it on throughout.
+    for (frame, chunk) in samples.chunks(FRAME_SAMPLES).enumerate() {
+        if expected[frame] {
+            assert!(!chunk.contains(&0), "silence within frame {}", frame);
+        }
+    }
+}
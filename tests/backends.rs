@@ -0,0 +1,132 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// Differential test of the execution backends: every bundled ROM runs on both, frame by frame with the
+// same seed and the same (pseudo-random) key presses, and the machines have to stay identical.
+
+use rs_8chip_interpret::interpreter::cpu::Backend;
+use rs_8chip_interpret::interpreter::cpu::CPU;
+use rs_8chip_interpret::interpreter::font::Font;
+use rs_8chip_interpret::interpreter::keypad::Keypad;
+use rs_8chip_interpret::interpreter::memory::Memory;
+use rs_8chip_interpret::interpreter::platform::Platform;
+use rs_8chip_interpret::interpreter::quirks::Quirks;
+use rs_8chip_interpret::interpreter::random;
+
+const FRAMES: u32 = 600;
+const IPS: u32 = 30;
+const SEED: u64 = 0x8c41;
+
+// Rewrites the immediate of its own `7xnn` every iteration, which the blocks have to notice.
+const SELF_MODIFYING: [u8; 14] = [
+    0xA2, 0x0B, // I = 0x20B
+    0x72, 0x01, // V2 += 1
+    0x80, 0x20, // V0 = V2
+    0xF0, 0x55, // [I] = V0
+    0x63, 0x00, // V3 = 0
+    0x71, 0x00, // V1 += (patched)
+    0x12, 0x00, // jump 0x200
+];
+
+const ROMS: [(&str, &[u8]); 9] = [
+    ("ibm", include_bytes!("../roms/ibm.ch8")),
+    ("chip8-logo", include_bytes!("../roms/1-chip8-logo.ch8")),
+    ("test_opcode", include_bytes!("../roms/test_opcode.ch8")),
+    ("corax+", include_bytes!("../roms/3-corax+.ch8")),
+    ("flags", include_bytes!("../roms/4-flags.ch8")),
+    ("quirks", include_bytes!("../roms/5-quirks.ch8")),
+    ("1dcell", include_bytes!("../roms/1dcell.ch8")),
+    ("pong", include_bytes!("../roms/pong.rom")),
+    ("self-modifying", &SELF_MODIFYING),
+];
+
+struct Machine {
+    cpu: CPU,
+    memory: Memory,
+    keypad: Keypad,
+}
+
+impl Machine {
+    fn new(rom: &[u8], quirks: Quirks, rng: &str, backend: Backend) -> Self {
+        let map = Platform::OriginalChip8.memory_map();
+        let mut memory = Memory::new(map);
+        memory.initialize(&Font::new());
+        memory.load_rom(rom).unwrap();
+
+        let mut cpu = CPU::new(&map);
+        cpu.set_quirks(quirks);
+        cpu.set_random_source(random::from_name(rng, SEED).unwrap());
+        cpu.set_backend(backend);
+        return Machine { cpu, memory, keypad: Keypad::new() };
+    }
+
+    // One 60Hz frame, the way the main loop runs it.
+    fn frame(&mut self, keys: u16) {
+        self.keypad.set_state(keys);
+        self.cpu.run(&mut self.memory, &mut self.keypad, IPS).unwrap();
+        if self.cpu.get_delay_timer() > 0 {
+            self.cpu.dec_delay_timer();
+        }
+        self.cpu.tick_random_source();
+        self.cpu.vblank();
+        if self.cpu.get_sound_timer() > 0 {
+            self.cpu.dec_sound_timer();
+        }
+    }
+}
+
+// Holds a random key combination for a few frames at a time, so ROMs waiting on Fx0A or a release get to see it.
+fn keys(frame: u32) -> u16 {
+    let mut x = (frame / 8) as u64 ^ SEED;
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    return if x.is_multiple_of(3) { 0 } else { 1 << (x % 16) };
+}
+
+fn compare(quirks: Quirks, rng: &str) {
+    for (name, rom) in ROMS.iter() {
+        let mut interpreter = Machine::new(rom, quirks, rng, Backend::Interpreter);
+        let mut blocks = Machine::new(rom, quirks, rng, Backend::Blocks);
+        for frame in 0..FRAMES {
+            interpreter.frame(keys(frame));
+            blocks.frame(keys(frame));
+            assert!(interpreter.cpu.get_state() == blocks.cpu.get_state(), "{}: CPU state differs at frame {}", name, frame);
+            assert!(interpreter.memory.get_ram() == blocks.memory.get_ram(), "{}: RAM differs at frame {}", name, frame);
+        }
+    }
+}
+
+#[test]
+fn backends_agree_chip8() {
+    compare(Quirks::from_preset("chip8").unwrap(), "default");
+}
+
+#[test]
+fn backends_agree_schip() {
+    compare(Quirks::from_preset("schip").unwrap(), "default");
+}
+
+#[test]
+fn backends_agree_vip_random() {
+    compare(Quirks::from_preset("chip8").unwrap(), "vip-like");
+}
@@ -0,0 +1,90 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// Both benchmark passes start from the state the ROM was loaded in.
+
+mod common;
+
+use rs_8chip_interpret::interpreter::bench;
+use rs_8chip_interpret::interpreter::bench::FamilyProfile;
+use rs_8chip_interpret::interpreter::bench::Limit;
+use rs_8chip_interpret::interpreter::keypad::Keypad;
+use rs_8chip_interpret::interpreter::random;
+
+#[test]
+fn passes_start_from_the_same_state() {
+    // Calls itself: 10 calls fit the 12 entry stack, 20 don't.
+    let (mut cpu, mut memory) = common::machine(&[0x22, 0x00]);
+    cpu.set_stack(Some(12), false);
+    let report = bench::run(&mut cpu, &mut memory, &mut Keypad::new(), 10, Limit::Cycles(10));
+    assert!(report.is_ok());
+    assert!(report.unwrap().to_string().starts_with("10 instructions, 1 frames"));
+}
+
+#[test]
+fn passes_restore_ram() {
+    // Returns without a call if it finds the byte it overwrites on its first run.
+    let (mut cpu, mut memory) = common::machine(&[
+        0xA2, 0x0A, // i := 0x20A
+        0xF0, 0x65, // load v0
+        0x30, 0x00, // if v0 != 0 then
+        0x00, 0xEE, // return, a stack underflow
+        0x12, 0x0C, // jump 0x20C
+        0x00,       // data
+        0x00,
+        0x60, 0x01, // v0 := 1
+        0xA2, 0x0A, // i := 0x20A
+        0xF0, 0x55, // save v0 over the data
+        0x12, 0x12, // jump 0x212
+    ]);
+    let report = bench::run(&mut cpu, &mut memory, &mut Keypad::new(), 10, Limit::Cycles(10));
+    assert!(report.is_ok());
+}
+
+#[test]
+fn passes_draw_the_same_random_numbers() {
+    // Takes one of two paths on every random bit, through 6xnn or through 7xnn.
+    let rom = [
+        0xC0, 0x01, // v0 := random 0x01
+        0x30, 0x00, // if v0 != 0 then
+        0x12, 0x0A, // jump 0x20A
+        0x61, 0x01, // v1 := 1
+        0x12, 0x00, // jump 0x200
+        0x71, 0x01, // v1 += 1
+        0x12, 0x00, // jump 0x200
+    ];
+    let run = |timed: bool| -> FamilyProfile {
+        let (mut cpu, mut memory) = common::machine(&rom);
+        cpu.set_random_source(random::from_name("default", 42).unwrap());
+        if timed {
+            return *bench::run(&mut cpu, &mut memory, &mut Keypad::new(), 10, Limit::Cycles(1000)).unwrap().get_families();
+        }
+        cpu.set_family_profiling(true);
+        cpu.run(&mut memory, &mut Keypad::new(), 1000).unwrap();
+        return *cpu.get_family_profile().unwrap();
+    };
+    // The timed pass took the same paths as a single run from the start.
+    let counts = |families: FamilyProfile| families.map(|(count, _)| count);
+    let (timed, single) = (counts(run(true)), counts(run(false)));
+    assert_eq!(timed, single);
+    assert!(timed[6] > 0 && timed[7] > 0);
+}
@@ -0,0 +1,147 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// The control-flow graph of a small fixed ROM, exactly as exported by chip8-cfg.
+
+use rs_8chip_interpret::interpreter::cfg::ControlFlowGraph;
+
+const ROM: [u8; 10] = [
+    0x60, 0x00, // v0 := 0
+    0x30, 0x00, // if v0 != 0 then
+    0x22, 0x08, // call 0x208
+    0x13, 0x00, // jump 0x300, outside the ROM
+    0x00, 0xEE, // 0x208: return
+];
+
+const DOT: &str = r#"digraph cfg {
+    node [shape=box, fontname="monospace"];
+    b200 [label="0x200  LD V0, 0x00\l0x202  SE V0, 0x00\l", peripheries=2];
+    b204 [label="0x204  CALL 0x208\l", peripheries=1];
+    b206 [label="0x206  JP 0x300\l", peripheries=1];
+    b208 [label="0x208  RET\l", peripheries=2];
+    x300 [label="0x300", style=dotted];
+    b200 -> b204 [style=solid, label="V0 != 0x00"];
+    b200 -> b206 [style=dashed, label="V0 == 0x00"];
+    b204 -> b206 [style=solid, label="next"];
+    b204 -> b208 [style=bold, color=darkgreen, label="call"];
+    b206 -> x300 [style=solid, color=blue, label="jump"];
+}
+"#;
+
+const JSON: &str = r#"{
+  "load_address": 512,
+  "blocks": [
+    {
+      "start": 512,
+      "instructions": [
+        {
+          "address": 512,
+          "opcode": 24576,
+          "text": "LD V0, 0x00"
+        },
+        {
+          "address": 514,
+          "opcode": 12288,
+          "text": "SE V0, 0x00"
+        }
+      ],
+      "entry": true
+    },
+    {
+      "start": 516,
+      "instructions": [
+        {
+          "address": 516,
+          "opcode": 8712,
+          "text": "CALL 0x208"
+        }
+      ],
+      "entry": false
+    },
+    {
+      "start": 518,
+      "instructions": [
+        {
+          "address": 518,
+          "opcode": 4864,
+          "text": "JP 0x300"
+        }
+      ],
+      "entry": false
+    },
+    {
+      "start": 520,
+      "instructions": [
+        {
+          "address": 520,
+          "opcode": 238,
+          "text": "RET"
+        }
+      ],
+      "entry": true
+    }
+  ],
+  "edges": [
+    {
+      "from": 512,
+      "to": 516,
+      "kind": "next",
+      "condition": "V0 != 0x00",
+      "outside": false
+    },
+    {
+      "from": 512,
+      "to": 518,
+      "kind": "skip",
+      "condition": "V0 == 0x00",
+      "outside": false
+    },
+    {
+      "from": 516,
+      "to": 518,
+      "kind": "next",
+      "outside": false
+    },
+    {
+      "from": 516,
+      "to": 520,
+      "kind": "call",
+      "outside": false
+    },
+    {
+      "from": 518,
+      "to": 768,
+      "kind": "jump",
+      "outside": true
+    }
+  ]
+}"#;
+
+#[test]
+fn dot() {
+    assert_eq!(ControlFlowGraph::new(&ROM, 0x200).to_dot(), DOT);
+}
+
+#[test]
+fn json() {
+    assert_eq!(ControlFlowGraph::new(&ROM, 0x200).to_json(), JSON);
+}
@@ -0,0 +1,38 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// Setup shared by the integration tests.
+
+use rs_8chip_interpret::interpreter::cpu::CPU;
+use rs_8chip_interpret::interpreter::font::Font;
+use rs_8chip_interpret::interpreter::memory::Memory;
+use rs_8chip_interpret::interpreter::platform::Platform;
+
+// An original CHIP-8 with the font in place and the ROM loaded, ready to run.
+pub fn machine(rom: &[u8]) -> (CPU, Memory) {
+    let map = Platform::OriginalChip8.memory_map();
+    let mut memory = Memory::new(map);
+    memory.initialize(&Font::new());
+    memory.load_rom(rom).unwrap();
+    let cpu = CPU::new(&map);
+    return (cpu, memory);
+}
@@ -0,0 +1,81 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// The coverage report of a short fixed run, exactly as written by --coverage.
+
+mod common;
+
+use rs_8chip_interpret::interpreter::coverage::Coverage;
+use rs_8chip_interpret::interpreter::cpu::Backend;
+use rs_8chip_interpret::interpreter::keypad::Keypad;
+
+const ROM: [u8; 16] = [
+    0xA2, 0x0E, // i := 0x20E
+    0xD0, 0x11, // sprite v0 v1 1, reads 0x20E
+    0x30, 0x00, // if v0 != 0 then, always skips
+    0x60, 0x01, // v0 := 1, never runs
+    0xF0, 0x55, // save v0, writes 0x20E
+    0x12, 0x0A, // jump 0x20A
+    0x00, 0xE0, // clear, never runs
+    0x80,       // sprite, read and written
+    0xFF,       // never used
+];
+
+const REPORT: &str = "\
+Coverage of the ROM at 0x200-0x20F (16 bytes)
+  executed       10 bytes   62.5%
+  read            1 bytes    6.2%
+  written         1 bytes    6.2%
+  untouched       5 bytes   31.2%
+  branches   0 of 1 skips went both ways
+
+x executed, r read, w written, ! skip that only went one way
+
+ x-- 0x200  A20E                    LD I, 0x20E
+ x-- 0x202  D011                    DRW V0, V1, 1
+!x-- 0x204  3000                    SE V0, 0x00      ; skipped 1, not skipped 0
+ --- 0x206  6001                    LD V0, 0x01
+ x-- 0x208  F055                    LD [I], V0
+ x-- 0x20A  120A                    JP 0x20A
+ --- 0x20C  00E0                    CLS
+ -rw 0x20E  80                      db 0x80
+ --- 0x20F  FF                      db 0xFF
+";
+
+fn report(backend: Backend) -> String {
+    let (mut cpu, mut memory) = common::machine(&ROM);
+    let map = memory.get_map();
+    cpu.set_backend(backend);
+    cpu.set_coverage(Some(Coverage::new(map.size, map.load_address, &ROM)));
+    cpu.run(&mut memory, &mut Keypad::new(), 20).unwrap();
+    return cpu.take_coverage().unwrap().report();
+}
+
+#[test]
+fn report_interpreter() {
+    assert_eq!(report(Backend::Interpreter), REPORT);
+}
+
+#[test]
+fn report_blocks() {
+    assert_eq!(report(Backend::Blocks), REPORT);
+}
@@ -0,0 +1,67 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// A ROM database or overrides file that can't be read is reported, and the bundled database stays.
+
+use std::fs;
+
+use rs_8chip_interpret::interpreter::database::RomDatabase;
+
+const IBM: &[u8] = include_bytes!("../roms/ibm.ch8");
+
+fn temp_path(name: &str) -> String {
+    return std::env::temp_dir().join(format!("rs-8chip-{}-{}", name, std::process::id())).to_string_lossy().to_string();
+}
+
+#[test]
+fn missing_database_directory() {
+    let mut database = RomDatabase::new();
+    let error = database.load(&temp_path("missing-database")).unwrap_err();
+    assert!(error.starts_with("failed to read"), "{}", error);
+    assert!(database.identify(IBM).is_some());
+}
+
+#[test]
+fn broken_database_keeps_the_bundled_one() {
+    let directory = temp_path("broken-database");
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(format!("{}/sha1-hashes.json", directory), "{}").unwrap();
+    fs::write(format!("{}/programs.json", directory), "[{").unwrap();
+    let mut database = RomDatabase::new();
+    let error = database.load(&directory).unwrap_err();
+    assert!(error.starts_with("invalid"), "{}", error);
+    assert!(database.identify(IBM).is_some());
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn missing_and_broken_overrides() {
+    let mut database = RomDatabase::new();
+    let error = database.load_overrides(&temp_path("missing-overrides.json")).unwrap_err();
+    assert!(error.starts_with("failed to read ROM overrides file"), "{}", error);
+
+    let path = temp_path("broken-overrides.json");
+    fs::write(&path, "{\"1ba5\": [").unwrap();
+    let error = database.load_overrides(&path).unwrap_err();
+    assert!(error.starts_with("invalid ROM overrides file"), "{}", error);
+    fs::remove_file(path).unwrap();
+}
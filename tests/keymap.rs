@@ -0,0 +1,63 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// Keymap files that can't be parsed are reported, not applied, and never overwritten.
+
+use std::fs;
+use std::path::PathBuf;
+
+use rs_8chip_interpret::interpreter::keypad::Keypad;
+
+const BROKEN: &str = "[keys]\n\"5\" = [\"W\"\n\n[rom.\"pong.rom\".keys]\n\"1\" = [\"Up\"]\n";
+
+fn keymap_file(name: &str, content: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("rs-8chip-{}-{}.toml", name, std::process::id()));
+    fs::write(&path, content).unwrap();
+    return path;
+}
+
+#[test]
+fn broken_keymap_isnt_loaded() {
+    let path = keymap_file("load", BROKEN);
+    let mut keypad = Keypad::new();
+    let error = keypad.load_keymap(path.to_str().unwrap(), "pong.rom").unwrap_err();
+    assert!(error.starts_with("invalid keymap file"), "{}", error);
+    for key in 0..16 {
+        assert!(keypad.get_bindings(key) == Keypad::new().get_bindings(key));
+    }
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn broken_keymap_isnt_overwritten() {
+    let path = keymap_file("save", BROKEN);
+    let error = Keypad::new().save_keymap(path.to_str().unwrap(), "pong.rom").unwrap_err();
+    assert!(error.contains("not overwriting it"), "{}", error);
+    assert_eq!(fs::read_to_string(&path).unwrap(), BROKEN);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn missing_keymap() {
+    let path = std::env::temp_dir().join(format!("rs-8chip-missing-{}.toml", std::process::id()));
+    assert!(Keypad::new().load_keymap(path.to_str().unwrap(), "pong.rom").is_err());
+}
@@ -0,0 +1,142 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// Tiny ROMs with one problem each, checked against the exact diagnostics chip8-lint reports.
+
+use rs_8chip_interpret::interpreter::lint::lint;
+use rs_8chip_interpret::interpreter::lint::Severity;
+use rs_8chip_interpret::interpreter::platform::Platform;
+
+fn diagnostics(rom: &[u8]) -> Vec<(u16, Severity, String)> {
+    return diagnostics_on(rom, Platform::OriginalChip8);
+}
+
+fn diagnostics_on(rom: &[u8], platform: Platform) -> Vec<(u16, Severity, String)> {
+    return lint(rom, platform).into_iter()
+        .map(|diagnostic| (diagnostic.address, diagnostic.severity, diagnostic.message))
+        .collect();
+}
+
+#[test]
+fn clean_rom() {
+    assert!(diagnostics(include_bytes!("../roms/ibm.ch8")).is_empty());
+}
+
+#[test]
+fn invalid_instruction() {
+    let rom = [
+        0x60, 0x01, // V0 = 1
+        0xF0, 0xFF, // not an instruction
+    ];
+    assert_eq!(diagnostics(&rom), vec![
+        (0x202, Severity::Error, "F0FF isn't a valid instruction, it halts the interpreter".to_string()),
+    ]);
+}
+
+#[test]
+fn uninitialized_read() {
+    let rom = [
+        0xA3, 0x00, // I = 0x300, past the ROM
+        0xD0, 0x15, // draw 5 rows from I
+        0x12, 0x04, // jump 0x204
+    ];
+    assert_eq!(diagnostics(&rom), vec![
+        (0x202, Severity::Warning, "reads 0x300, which is outside the ROM and nothing seems to write there".to_string()),
+    ]);
+}
+
+#[test]
+fn code_flowing_into_data() {
+    let rom = [
+        0xA2, 0x06, // I = 0x206
+        0xD0, 0x11, // draw the sprite at 0x206
+        0x60, 0x00, // V0 = 0, then on into the sprite
+        0x12, 0x06, // sprite row, and a jump to itself when executed
+    ];
+    assert_eq!(diagnostics(&rom), vec![
+        (0x204, Severity::Warning, "execution runs into data at 0x206".to_string()),
+    ]);
+}
+
+#[test]
+fn unreachable_bytes() {
+    let rom = [
+        0x12, 0x00, // jump 0x200
+        0x00, 0xE0, // never reached
+        0x00, 0xE0,
+    ];
+    assert_eq!(diagnostics(&rom), vec![
+        (0x202, Severity::Note, "4 bytes (0x202-0x205) are never executed or read at a known address".to_string()),
+    ]);
+}
+
+#[test]
+fn recursion() {
+    let rom = [
+        0x22, 0x04, // call 0x204
+        0x12, 0x02, // jump 0x202
+        0x22, 0x04, // call 0x204, from itself
+        0x00, 0xEE, // return
+    ];
+    assert_eq!(diagnostics(&rom), vec![
+        (0x204, Severity::Warning, "recursive calls (0x204 -> 0x204) can overflow the stack".to_string()),
+    ]);
+}
+
+#[test]
+fn control_flow_leaving_the_rom() {
+    let rom = [
+        0x60, 0x01, // V0 = 1
+        0x13, 0x00, // jump 0x300, past the ROM
+    ];
+    assert_eq!(diagnostics(&rom), vec![
+        (0x202, Severity::Warning, "control flow leaves the ROM, to 0x300".to_string()),
+    ]);
+}
+
+#[test]
+fn superchip_instructions() {
+    let rom = [
+        0x00, 0xFF, // high resolution
+        0x00, 0xFE, // low resolution
+        0x12, 0x04, // jump 0x204
+    ];
+    // Valid on SUPER-CHIP, only this interpreter can't run them.
+    assert_eq!(diagnostics_on(&rom, Platform::Superchip), vec![
+        (0x200, Severity::Warning, "00FF (high resolution) is a superchip1 instruction this interpreter doesn't implement, it halts here".to_string()),
+        (0x202, Severity::Warning, "00FE (low resolution) is a superchip1 instruction this interpreter doesn't implement, it halts here".to_string()),
+    ]);
+    assert_eq!(diagnostics(&rom), vec![
+        (0x200, Severity::Error, "00FF (high resolution) is a superchip1 instruction, originalChip8 doesn't have it".to_string()),
+        (0x202, Severity::Error, "00FE (low resolution) is a superchip1 instruction, originalChip8 doesn't have it".to_string()),
+    ]);
+}
+
+#[test]
+fn rom_filling_xochip_memory() {
+    // Ends at 0xFFFF, the last byte of XO-CHIP's 64K.
+    let mut rom = vec![0; 0x10000 - 0x200];
+    rom[..2].copy_from_slice(&[0x12, 0x00]); // jump 0x200
+    assert_eq!(diagnostics_on(&rom, Platform::XoChip), vec![
+        (0x202, Severity::Note, "65022 bytes (0x202-0xFFFF) are never executed or read at a known address".to_string()),
+    ]);
+}
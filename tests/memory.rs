@@ -0,0 +1,115 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// Accesses past the end of RAM under each bounds policy, and accesses to the interpreter's region
+// below the load address under each reserved access setting.
+
+use rs_8chip_interpret::interpreter::font::Font;
+use rs_8chip_interpret::interpreter::memory::BoundsPolicy;
+use rs_8chip_interpret::interpreter::memory::Memory;
+use rs_8chip_interpret::interpreter::memory::ReservedAccess;
+use rs_8chip_interpret::interpreter::platform::Platform;
+
+const ROM: [u8; 4] = [0x12, 0x00, 0xAB, 0xCD];
+
+fn memory(bounds: BoundsPolicy, reserved: ReservedAccess) -> Memory {
+    let mut map = Platform::OriginalChip8.memory_map();
+    map.bounds = bounds;
+    map.reserved = reserved;
+    let mut memory = Memory::new(map);
+    memory.initialize(&Font::new());
+    memory.load_rom(&ROM).unwrap();
+    return memory;
+}
+
+#[test]
+fn wrap_and_mirror_go_around_the_end() {
+    for bounds in [BoundsPolicy::Wrap, BoundsPolicy::Mirror] {
+        let mut memory = memory(bounds, ReservedAccess::ReadWrite);
+        let size = memory.get_ram().len();
+        // I = 0xFFE with n = 4 reads 0xFFE, 0xFFF, 0x000 and 0x001.
+        memory.set_from_index(size - 1, 0x42).unwrap();
+        assert_eq!(memory.get_from_index(size - 1), Some(0x42));
+        assert_eq!(memory.get_from_index(size), Some(memory.get_ram()[0]));
+        assert_eq!(memory.get_from_index(size + 1), Some(memory.get_ram()[1]));
+        assert_eq!(memory.get_wrap_count(), 2);
+
+        assert_eq!(memory.set_from_index(size + 0x202, 0x99), Some(()));
+        assert_eq!(memory.get_ram()[0x202], 0x99);
+        assert_eq!(memory.get_wrap_count(), 3);
+    }
+}
+
+#[test]
+fn fault_refuses_accesses_past_the_end() {
+    let mut memory = memory(BoundsPolicy::Fault, ReservedAccess::ReadWrite);
+    let size = memory.get_ram().len();
+    let ram = memory.get_ram().to_vec();
+    assert_eq!(memory.get_from_index(size - 1), Some(0));
+    assert_eq!(memory.get_from_index(size), None);
+    assert_eq!(memory.get_from_index(size + 0x200), None);
+    assert_eq!(memory.set_from_index(size, 0x42), None);
+    assert!(memory.get_ram() == ram.as_slice());
+    assert_eq!(memory.get_wrap_count(), 0);
+}
+
+#[test]
+fn reserved_region_read_write() {
+    let mut memory = memory(BoundsPolicy::Wrap, ReservedAccess::ReadWrite);
+    memory.set_from_index(0x1FF, 0x42).unwrap();
+    memory.set_from_index(0x000, 0x24).unwrap();
+    assert_eq!(memory.get_from_index(0x1FF), Some(0x42));
+    assert_eq!(memory.get_from_index(0x000), Some(0x24));
+}
+
+#[test]
+fn reserved_region_read_only() {
+    let mut memory = memory(BoundsPolicy::Wrap, ReservedAccess::ReadOnly);
+    let font = Font::new();
+    // Writes are accepted but ignored, the font and the rest of the region stay readable.
+    assert_eq!(memory.set_from_index(0x000, 0x24), Some(()));
+    assert_eq!(memory.set_from_index(0x1FF, 0x42), Some(()));
+    assert_eq!(memory.get_from_index(0x000), Some(font.small[0]));
+    assert_eq!(memory.get_from_index(0x1FF), Some(0));
+    assert!(!memory.has_writes());
+    // The program itself is still writable.
+    memory.set_from_index(0x202, 0x42).unwrap();
+    assert_eq!(memory.get_from_index(0x202), Some(0x42));
+}
+
+#[test]
+fn reserved_region_none() {
+    let mut memory = memory(BoundsPolicy::Wrap, ReservedAccess::None);
+    let font = Font::new();
+    let size = font.size();
+    // Only the font can be read, everything else below the load address reads as 0.
+    assert_eq!(memory.get_from_index(0x000), Some(font.small[0]));
+    assert_eq!(memory.get_from_index(size - 1), Some(memory.get_ram()[size - 1]));
+    let mut ram = memory.get_ram().to_vec();
+    ram[0x1FF] = 0x42;
+    memory.set_ram(&ram);
+    assert_eq!(memory.get_from_index(0x1FF), Some(0));
+    assert_eq!(memory.set_from_index(0x000, 0x24), Some(()));
+    assert_eq!(memory.get_ram()[0x000], font.small[0]);
+    assert!(!memory.has_writes());
+    assert_eq!(memory.get_from_index(0x203), Some(0xCD));
+}
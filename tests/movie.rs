@@ -0,0 +1,85 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// Movies keep the settings they were recorded with, and broken movie files are errors.
+
+use std::fs;
+
+use rs_8chip_interpret::interpreter::movie::Movie;
+use rs_8chip_interpret::interpreter::platform::Platform;
+use rs_8chip_interpret::interpreter::quirks::Quirks;
+
+fn movie_file(name: &str) -> String {
+    return std::env::temp_dir().join(format!("rs-8chip-{}-{}.movie", name, std::process::id())).to_string_lossy().to_string();
+}
+
+fn recorded() -> Movie {
+    let mut movie = Movie::new("vip-like", 1234, Platform::OriginalChip8, 15, Quirks::from_preset("chip8").unwrap());
+    movie.hash = Some(0x9f1c);
+    movie.inputs = vec![0x0000, 0x0020, 0x8001];
+    return movie;
+}
+
+#[test]
+fn round_trip() {
+    let path = movie_file("round-trip");
+    let movie = recorded();
+    movie.save(&path);
+    let loaded = Movie::load(&path).unwrap();
+    fs::remove_file(path).unwrap();
+    assert_eq!((loaded.rng.as_str(), loaded.seed, loaded.hash), ("vip-like", 1234, Some(0x9f1c)));
+    assert_eq!((loaded.platform, loaded.ips, loaded.quirks), (movie.platform, movie.ips, movie.quirks));
+    assert_eq!(loaded.inputs, movie.inputs);
+}
+
+#[test]
+fn other_settings_are_refused() {
+    let movie = recorded();
+    let quirks = Quirks::from_preset("chip8").unwrap();
+    assert!(movie.check(Platform::OriginalChip8, 15, quirks).is_ok());
+    assert!(movie.check(Platform::Superchip, 15, quirks).unwrap_err().ends_with("add --platform originalChip8"));
+    assert!(movie.check(Platform::OriginalChip8, 9, quirks).unwrap_err().ends_with("add --ips 15"));
+    let mut other = quirks;
+    other.set("clip", false);
+    assert!(movie.check(Platform::OriginalChip8, 15, other).unwrap_err().ends_with("add --quirk clip=on"));
+}
+
+#[test]
+fn broken_movies_are_errors() {
+    assert!(Movie::load(&movie_file("missing")).err().unwrap().starts_with("failed to read"));
+    let cases = [
+        ("not a movie\n", "is not a movie file"),
+        ("chip8-movie 1\nrng default\nseed x\n", "invalid seed \"x\""),
+        ("chip8-movie 1\nrng default\nseed 1\nplatform originalChip8\nips 15\n0000\n", "has no quirks line"),
+        ("chip8-movie 1\nrng default\nseed 1\nplatform originalChip8\nips 15\nquirks clip=on\n", "invalid quirks \"clip=on\""),
+        ("chip8-movie 1\nrng default\nseed 1\nplatform nes\n", "invalid platform \"nes\""),
+        ("chip8-movie 1\nrng default\nseed 1\nzzzz\n", "invalid input \"zzzz\""),
+    ];
+    for (index, (content, error)) in cases.iter().enumerate() {
+        let path = movie_file(&format!("broken-{}", index));
+        fs::write(&path, content).unwrap();
+        let result = Movie::load(&path);
+        fs::remove_file(&path).unwrap();
+        let message = result.err().unwrap();
+        assert!(message.contains(error), "{}", message);
+    }
+}
@@ -0,0 +1,66 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// The collapsed call stacks of a short fixed run, exactly as written by --profile-stacks.
+
+mod common;
+
+use rs_8chip_interpret::interpreter::cpu::Backend;
+use rs_8chip_interpret::interpreter::keypad::Keypad;
+use rs_8chip_interpret::interpreter::profiler::Profiler;
+
+const ROM: [u8; 16] = [
+    0x22, 0x08, // call 0x208
+    0x22, 0x0C, // call 0x20C
+    0x12, 0x04, // jump 0x204
+    0x00, 0x00,
+    0x22, 0x0C, // 0x208: call 0x20C
+    0x00, 0xEE, // return
+    0x60, 0x01, // 0x20C: v0 := 1
+    0x00, 0xEE, // return
+];
+
+// Main calls 0x208 (which calls 0x20C) and 0x20C, then spins 4 times.
+const STACKS: &str = "\
+main 6
+main;sub_208 2
+main;sub_208;sub_20C 2
+main;sub_20C 2
+";
+
+fn collapsed_stacks(backend: Backend) -> String {
+    let (mut cpu, mut memory) = common::machine(&ROM);
+    cpu.set_backend(backend);
+    cpu.set_profiler(Some(Profiler::new(memory.get_map().size)));
+    cpu.run(&mut memory, &mut Keypad::new(), 12).unwrap();
+    return cpu.take_profiler().unwrap().collapsed_stacks();
+}
+
+#[test]
+fn collapsed_stacks_interpreter() {
+    assert_eq!(collapsed_stacks(Backend::Interpreter), STACKS);
+}
+
+#[test]
+fn collapsed_stacks_blocks() {
+    assert_eq!(collapsed_stacks(Backend::Blocks), STACKS);
+}
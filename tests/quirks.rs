@@ -0,0 +1,100 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// Runs Timendus' quirks test (roms/5-quirks.ch8) on every platform it knows about and reads the
+// results off the screen: one line per quirk with "ON" or "OFF" and a checkmark if the behaviour
+// matched the platform the test was told to expect.
+
+use rs_8chip_interpret::interpreter::cpu::CPU;
+use rs_8chip_interpret::interpreter::cpu::HEIGHT;
+use rs_8chip_interpret::interpreter::cpu::WIDTH;
+use rs_8chip_interpret::interpreter::keypad::Keypad;
+use rs_8chip_interpret::interpreter::memory::Memory;
+use rs_8chip_interpret::interpreter::platform::Platform;
+
+const ROM: &[u8] = include_bytes!("../roms/5-quirks.ch8");
+const FRAMES: u32 = 600;
+const IPS: u32 = 100;
+
+// The test skips its menu if 0x1FF holds the platform to test: 1 for CHIP-8, 2 for SUPER-CHIP,
+// 3 for XO-CHIP. The ETI-660 (loads at 0x600) and modernChip8 (no matching mode) can't be tested.
+const PLATFORMS: [(Platform, u8); 6] = [
+    (Platform::OriginalChip8, 1),
+    (Platform::HybridVip, 1),
+    (Platform::Chip48, 2),
+    (Platform::Superchip1, 2),
+    (Platform::Superchip, 2),
+    (Platform::XoChip, 3),
+];
+
+// The result lines, top to bottom.
+const LINES: [&str; 6] = ["vf-reset", "memory", "display-wait", "clip", "shift", "jump"];
+
+const ON: [&str; 4] = ["###.##.....", "#.#.#.#....", "#.#.#.#....", "###.#.#...."];
+const OFF: [&str; 4] = ["###.###.###", "#.#.#...#..", "#.#.##..##.", "###.#...#.."];
+const CHECKMARK: [&str; 4] = ["...", "#.#", "##.", "#.."];
+
+fn run(platform: Platform, choice: u8) -> [[bool; WIDTH]; HEIGHT] {
+    let map = platform.memory_map();
+    let mut memory = Memory::new(map);
+    memory.initialize(&platform.font());
+    memory.load_rom(ROM).unwrap();
+    let mut ram = memory.get_ram().to_vec();
+    ram[0x1FF] = choice;
+    memory.set_ram(&ram);
+
+    let mut cpu = CPU::new(&map);
+    cpu.set_quirks(platform.quirks());
+    let mut keypad = Keypad::new();
+    for _ in 0..FRAMES {
+        cpu.run(&mut memory, &mut keypad, IPS).unwrap();
+        if cpu.get_delay_timer() > 0 {
+            cpu.dec_delay_timer();
+        }
+        cpu.vblank();
+        if cpu.get_sound_timer() > 0 {
+            cpu.dec_sound_timer();
+        }
+    }
+    return *cpu.get_buffer();
+}
+
+fn matches(buffer: &[[bool; WIDTH]; HEIGHT], x: usize, y: usize, glyph: &[&str]) -> bool {
+    return glyph.iter().enumerate().all(|(row, pixels)| {
+        pixels.chars().enumerate().all(|(column, pixel)| buffer[y + row][x + column] == (pixel == '#'))
+    });
+}
+
+#[test]
+fn quirks_test_passes_on_every_platform() {
+    for (platform, choice) in PLATFORMS {
+        let buffer = run(platform, choice);
+        let quirks = platform.quirks();
+        let expected = [quirks.vf_reset, quirks.memory, quirks.display_wait, quirks.clip, quirks.shift, quirks.jump];
+        for (line, name) in LINES.iter().enumerate() {
+            let y = 1 + 5 * line;
+            let label = if expected[line] { ON } else { OFF };
+            assert!(matches(&buffer, 44, y, &label), "{}: {} should read {}", platform.id(), name, if expected[line] { "ON" } else { "OFF" });
+            assert!(matches(&buffer, 59, y, &CHECKMARK), "{}: {} failed", platform.id(), name);
+        }
+    }
+}
@@ -0,0 +1,69 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// GIF recordings keep a delay viewers play back as is, raw recordings keep every frame.
+
+use rs_8chip_interpret::interpreter::cpu::HEIGHT;
+use rs_8chip_interpret::interpreter::cpu::WIDTH;
+use rs_8chip_interpret::interpreter::display::DEFAULT_PALETTE;
+use rs_8chip_interpret::interpreter::recorder::Recorder;
+
+const FRAMES: usize = 60;
+
+fn record(extension: &str) -> Vec<u8> {
+    let path = std::env::temp_dir().join(format!("rs-8chip-recording-{}.{}", std::process::id(), extension));
+    let mut recorder = Recorder::new(path.to_str().unwrap(), DEFAULT_PALETTE).unwrap();
+    let mut pixels = [[false; WIDTH]; HEIGHT];
+    for frame in 0..FRAMES {
+        pixels[0][frame % WIDTH] = true;
+        recorder.capture(&pixels);
+    }
+    recorder.finish();
+    let data = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    return data;
+}
+
+#[test]
+fn gif_plays_at_50_frames_per_second() {
+    let data = record("gif");
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::Indexed);
+    let mut decoder = options.read_info(data.as_slice()).unwrap();
+    let mut delays = Vec::new();
+    while let Some(frame) = decoder.read_next_frame().unwrap() {
+        delays.push(frame.delay);
+    }
+    // A second of emulation is a second of GIF.
+    assert_eq!(delays, vec![2; 50]);
+}
+
+#[test]
+fn raw_keeps_every_frame() {
+    assert_eq!(record("rgb").len(), FRAMES * WIDTH * HEIGHT * 3);
+}
+
+#[test]
+fn unwritable_path_is_an_error() {
+    let path = std::env::temp_dir().join("rs-8chip-missing-directory").join("capture.gif");
+    assert!(Recorder::new(path.to_str().unwrap(), DEFAULT_PALETTE).is_err());
+}
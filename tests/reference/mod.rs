@@ -0,0 +1,219 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// Reference model of the CHIP-8, written from the instruction set description (Cowgod's reference and
+// Tobias V. Langhoff's guide) rather than from `CPU`, so the two can be checked against each other.
+// It is deliberately simple: 4K of RAM that wraps around, a 16-entry stack, the font at 0x000 and no timing.
+
+pub const RAM_SIZE: usize = 0x1000;
+pub const STACK_DEPTH: usize = 16;
+pub const WIDTH: usize = 64;
+pub const HEIGHT: usize = 32;
+// The big font follows the 80 bytes of the small one.
+const BIG_FONT: u16 = 80;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Quirks {
+    pub vf_reset: bool,
+    pub memory: bool,
+    pub shift: bool,
+    pub jump: bool,
+    pub clip: bool,
+}
+
+// What a step did, besides changing the state.
+#[derive(Debug, PartialEq)]
+pub enum Step {
+    Done,
+    // Cxnn: vX has to be a random number masked with nn, the model can't know which one.
+    Random { x: usize, mask: u8 },
+    // Call with a full stack or return with an empty one, the machine halts.
+    Fault,
+}
+
+#[derive(Clone)]
+pub struct Model {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub stack: Vec<u16>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub ram: Vec<u8>,
+    pub screen: [[bool; WIDTH]; HEIGHT],
+    pub quirks: Quirks,
+}
+
+// Whether the opcode is part of the CHIP-8 instruction set (plus SUPER-CHIP's Fx30).
+pub fn is_valid(opcode: u16) -> bool {
+    let nn = opcode & 0xFF;
+    return match opcode >> 12 {
+        0x0 => opcode == 0x00E0 || opcode == 0x00EE,
+        0x5 | 0x9 => opcode & 0xF == 0,
+        0x8 => matches!(opcode & 0xF, 0x0..=0x7 | 0xE),
+        0xE => nn == 0x9E || nn == 0xA1,
+        0xF => matches!(nn, 0x07 | 0x0A | 0x15 | 0x18 | 0x1E | 0x29 | 0x30 | 0x33 | 0x55 | 0x65),
+        _ => true,
+    };
+}
+
+impl Model {
+    fn load(&self, address: u16) -> u8 {
+        return self.ram[address as usize % RAM_SIZE];
+    }
+
+    fn store(&mut self, address: u16, value: u8) {
+        self.ram[address as usize % RAM_SIZE] = value;
+    }
+
+    pub fn opcode(&self) -> u16 {
+        return (self.load(self.pc) as u16) << 8 | self.load(self.pc.wrapping_add(1)) as u16;
+    }
+
+    // Executes the instruction at pc, `keys` has bit n set while key n is held down.
+    pub fn step(&mut self, keys: u16) -> Step {
+        let opcode = self.opcode();
+        let x = (opcode >> 8 & 0xF) as usize;
+        let y = (opcode >> 4 & 0xF) as usize;
+        let n = opcode & 0xF;
+        let nn = (opcode & 0xFF) as u8;
+        let nnn = opcode & 0xFFF;
+        let next = self.pc + 2;
+        let skip = self.pc + 4;
+        let (vx, vy) = (self.v[x], self.v[y]);
+
+        self.pc = next;
+        match opcode >> 12 {
+            0x0 if opcode == 0x00E0 => self.screen = [[false; WIDTH]; HEIGHT],
+            0x0 => match self.stack.pop() {
+                Some(address) => self.pc = address,
+                None => return Step::Fault,
+            },
+            0x1 => self.pc = nnn,
+            0x2 => {
+                if self.stack.len() == STACK_DEPTH {
+                    return Step::Fault;
+                }
+                self.stack.push(next);
+                self.pc = nnn;
+            },
+            0x3 => if vx == nn { self.pc = skip },
+            0x4 => if vx != nn { self.pc = skip },
+            0x5 => if vx == vy { self.pc = skip },
+            0x9 => if vx != vy { self.pc = skip },
+            0x6 => self.v[x] = nn,
+            0x7 => self.v[x] = vx.wrapping_add(nn),
+            0x8 => {
+                // The flag is written last, so it wins when x is F.
+                let (result, flag) = match n {
+                    0x0 => (vy, None),
+                    0x1 => (vx | vy, self.quirks.vf_reset.then_some(0)),
+                    0x2 => (vx & vy, self.quirks.vf_reset.then_some(0)),
+                    0x3 => (vx ^ vy, self.quirks.vf_reset.then_some(0)),
+                    0x4 => (vx.wrapping_add(vy), Some((vx as u16 + vy as u16 > 0xFF) as u8)),
+                    0x5 => (vx.wrapping_sub(vy), Some((vx >= vy) as u8)),
+                    0x7 => (vy.wrapping_sub(vx), Some((vy >= vx) as u8)),
+                    0x6 => {
+                        let source = if self.quirks.shift { vx } else { vy };
+                        (source >> 1, Some(source & 1))
+                    },
+                    _ => {
+                        let source = if self.quirks.shift { vx } else { vy };
+                        (source << 1, Some(source >> 7))
+                    },
+                };
+                self.v[x] = result;
+                if let Some(flag) = flag {
+                    self.v[0xF] = flag;
+                }
+            },
+            0xA => self.i = nnn,
+            0xB => self.pc = nnn + self.v[if self.quirks.jump { x } else { 0 }] as u16,
+            0xC => return Step::Random { x, mask: nn },
+            0xD => self.draw(vx as usize, vy as usize, n as usize),
+            0xE => {
+                let pressed = keys & 1 << (vx & 0xF) != 0;
+                if pressed == (nn == 0x9E) {
+                    self.pc = skip;
+                }
+            },
+            _ => match nn {
+                0x07 => self.v[x] = self.delay_timer,
+                0x0A => match (0..16).find(|key| keys & 1 << key != 0) {
+                    Some(key) => self.v[x] = key as u8,
+                    // Waits on this instruction until a key is down.
+                    None => self.pc -= 2,
+                },
+                0x15 => self.delay_timer = vx,
+                0x18 => self.sound_timer = vx,
+                0x1E => self.i = (self.i + vx as u16) % RAM_SIZE as u16,
+                0x29 => self.i = (vx & 0xF) as u16 * 5,
+                0x30 => self.i = BIG_FONT + (vx & 0xF) as u16 * 10,
+                0x33 => {
+                    self.store(self.i, vx / 100);
+                    self.store(self.i + 1, vx / 10 % 10);
+                    self.store(self.i + 2, vx % 10);
+                },
+                0x55 | 0x65 => {
+                    for register in 0..=x {
+                        let address = self.i + register as u16;
+                        if nn == 0x55 {
+                            self.store(address, self.v[register]);
+                        } else {
+                            self.v[register] = self.load(address);
+                        }
+                    }
+                    if self.quirks.memory {
+                        self.i = (self.i + x as u16 + 1) % RAM_SIZE as u16;
+                    }
+                },
+                _ => unreachable!("invalid opcode {:04X}", opcode),
+            },
+        }
+        return Step::Done;
+    }
+
+    // Dxyn: XORs an 8xN sprite from I onto the screen, VF is set when a pixel is turned off.
+    fn draw(&mut self, x: usize, y: usize, rows: usize) {
+        let (x, y) = (x % WIDTH, y % HEIGHT);
+        self.v[0xF] = 0;
+        for row in 0..rows {
+            if self.quirks.clip && y + row >= HEIGHT {
+                break;
+            }
+            let sprite = self.load(self.i + row as u16);
+            for column in 0..8 {
+                if self.quirks.clip && x + column >= WIDTH {
+                    break;
+                }
+                if sprite & 0x80 >> column == 0 {
+                    continue;
+                }
+                let pixel = &mut self.screen[(y + row) % HEIGHT][(x + column) % WIDTH];
+                if *pixel {
+                    self.v[0xF] = 1;
+                }
+                *pixel = !*pixel;
+            }
+        }
+    }
+}
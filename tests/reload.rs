@@ -0,0 +1,118 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// Reloading a ROM while it runs (--watch): the new code has to run, not whatever the decoded
+// instruction cache or the compiled blocks still hold for the same addresses.
+
+mod common;
+
+use rs_8chip_interpret::interpreter::coverage::Coverage;
+use rs_8chip_interpret::interpreter::cpu::Backend;
+use rs_8chip_interpret::interpreter::cpu::CPU;
+use rs_8chip_interpret::interpreter::font::Font;
+use rs_8chip_interpret::interpreter::keypad::Keypad;
+use rs_8chip_interpret::interpreter::memory::Memory;
+use rs_8chip_interpret::interpreter::watcher::reload;
+use rs_8chip_interpret::interpreter::watcher::Reload;
+
+const ROM_A: [u8; 4] = [
+    0x60, 0xAA, // V0 = 0xAA
+    0x12, 0x02, // jump 0x202
+];
+
+const ROM_B: [u8; 6] = [
+    0x60, 0xBB, // V0 = 0xBB
+    0x61, 0xCC, // V1 = 0xCC
+    0x12, 0x04, // jump 0x204
+];
+
+fn start(backend: Backend) -> (CPU, Memory, Keypad) {
+    let (mut cpu, mut memory) = common::machine(&ROM_A);
+    cpu.set_backend(backend);
+    let mut keypad = Keypad::new();
+    cpu.run(&mut memory, &mut keypad, 100).unwrap();
+    assert_eq!(cpu.get_state().v[0], 0xAA);
+    return (cpu, memory, keypad);
+}
+
+fn reset_runs_new_rom(backend: Backend) {
+    let (mut cpu, mut memory, mut keypad) = start(backend);
+    reload(Reload::Reset, &mut cpu, &mut memory, &Font::new(), &ROM_A, &ROM_B).unwrap();
+    assert_eq!(cpu.get_state().pc, 0x200);
+    cpu.run(&mut memory, &mut keypad, 100).unwrap();
+    let state = cpu.get_state();
+    assert_eq!((state.v[0], state.v[1], state.pc), (0xBB, 0xCC, 0x204));
+}
+
+fn patch_keeps_registers(backend: Backend) {
+    let (mut cpu, mut memory, mut keypad) = start(backend);
+    reload(Reload::Patch, &mut cpu, &mut memory, &Font::new(), &ROM_A, &ROM_B).unwrap();
+    // Still looping at 0x202, which now holds V1 = 0xCC.
+    cpu.run(&mut memory, &mut keypad, 100).unwrap();
+    let state = cpu.get_state();
+    assert_eq!((state.v[0], state.v[1], state.pc), (0xAA, 0xCC, 0x204));
+}
+
+#[test]
+fn reset_runs_new_rom_interpreter() {
+    reset_runs_new_rom(Backend::Interpreter);
+}
+
+#[test]
+fn reset_runs_new_rom_blocks() {
+    reset_runs_new_rom(Backend::Blocks);
+}
+
+#[test]
+fn patch_keeps_registers_interpreter() {
+    patch_keeps_registers(Backend::Interpreter);
+}
+
+#[test]
+fn patch_keeps_registers_blocks() {
+    patch_keeps_registers(Backend::Blocks);
+}
+
+#[test]
+fn failed_reload_keeps_program() {
+    let (mut cpu, mut memory, _) = start(Backend::Interpreter);
+    let ram = memory.get_ram().to_vec();
+    assert!(reload(Reload::Reset, &mut cpu, &mut memory, &Font::new(), &ROM_A, &[0; 4000]).is_err());
+    assert!(memory.get_ram() == ram.as_slice());
+    assert_eq!(cpu.get_state().pc, 0x202);
+}
+
+#[test]
+fn coverage_follows_the_new_rom() {
+    for mode in [Reload::Reset, Reload::Patch] {
+        let (mut cpu, mut memory, mut keypad) = start(Backend::Interpreter);
+        let map = memory.get_map();
+        cpu.set_coverage(Some(Coverage::new(map.size, map.load_address, &ROM_A)));
+        cpu.run(&mut memory, &mut keypad, 10).unwrap();
+        reload(mode, &mut cpu, &mut memory, &Font::new(), &ROM_A, &ROM_B).unwrap();
+        cpu.run(&mut memory, &mut keypad, 10).unwrap();
+        let report = cpu.take_coverage().unwrap().report();
+        assert!(report.starts_with("Coverage of the ROM at 0x200-0x205 (6 bytes)"), "{}", report);
+        assert!(report.contains(" 0x202  61CC "), "{}", report);
+        assert!(!report.contains("1202"), "{}", report);
+    }
+}
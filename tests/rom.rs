@@ -0,0 +1,134 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// ROM loading: hex dumps, zip archives, Octo cartridges and ROMs that don't fit in memory.
+
+use std::borrow::Cow;
+use std::io::Cursor;
+use std::io::Write;
+
+use rs_8chip_interpret::interpreter::memory::Memory;
+use rs_8chip_interpret::interpreter::platform::Platform;
+use rs_8chip_interpret::interpreter::rom::RomError;
+use rs_8chip_interpret::interpreter::rom::RomLoader;
+
+fn decode(name: &str, data: &[u8]) -> Result<Vec<u8>, RomError> {
+    return RomLoader::new().decode(name, data.to_vec());
+}
+
+#[test]
+fn hex_dump() {
+    let text = b"# IBM logo, first bytes\n0200: 00E0 A22A ; clear, I = 0x22A\n0x60, 0x0C, $61 // V0, V1\n08\n";
+    assert_eq!(decode("ibm.hex", text).unwrap(), vec![0x00, 0xE0, 0xA2, 0x2A, 0x60, 0x0C, 0x61, 0x08]);
+}
+
+#[test]
+fn hex_dump_odd_nibble_count() {
+    let error = decode("rom.txt", b"00E0\nA22 6000\n").unwrap_err();
+    assert!(matches!(error, RomError::Hex { line: 2, ref token } if token == "A22"), "{}", error);
+}
+
+#[test]
+fn printable_binary_isnt_hex() {
+    // "A2 6F" is all printable, but only .hex and .txt files are hex dumps.
+    assert_eq!(decode("rom.ch8", b"A2 6F").unwrap(), b"A2 6F".to_vec());
+}
+
+#[test]
+fn forced_hex() {
+    let mut loader = RomLoader::new();
+    loader.set_hex(true);
+    assert_eq!(loader.decode("-", b"00E0 1200".to_vec()).unwrap(), vec![0x00, 0xE0, 0x12, 0x00]);
+}
+
+#[test]
+fn empty_rom() {
+    assert!(matches!(decode("rom.ch8", b""), Err(RomError::Empty)));
+    assert!(matches!(decode("rom.hex", b"# nothing but a comment\n"), Err(RomError::Empty)));
+}
+
+fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, data) in files {
+        writer.start_file(*name, zip::write::FileOptions::default()).unwrap();
+        writer.write_all(data).unwrap();
+    }
+    return writer.finish().unwrap().into_inner();
+}
+
+#[test]
+fn zip_single_entry() {
+    let archive = zip(&[("pong", &[0x12, 0x00])]);
+    assert_eq!(decode("pong.zip", &archive).unwrap(), vec![0x12, 0x00]);
+}
+
+#[test]
+fn zip_several_entries() {
+    // The readme is skipped, the first ROM by name wins and hex entries are parsed.
+    let archive = zip(&[("readme.md", b"# Pong"), ("b.ch8", &[0x13, 0x00]), ("a.hex", b"1200")]);
+    assert_eq!(decode("pong.zip", &archive).unwrap(), vec![0x12, 0x00]);
+}
+
+#[test]
+fn zip_without_rom() {
+    assert!(matches!(decode("empty.zip", &zip(&[])), Err(RomError::Zip(_))));
+    assert!(matches!(decode("docs.zip", &zip(&[("readme.md", b"a"), ("notes.md", b"b")])), Err(RomError::Zip(_))));
+}
+
+// Octo's encoding: a length-prefixed JSON payload, two bits per pixel, most significant bits first.
+fn cartridge(program: &str) -> Vec<u8> {
+    let json = serde_json::json!({ "program": program, "options": {} }).to_string();
+    let mut payload = (json.len() as u32).to_be_bytes().to_vec();
+    payload.extend(json.as_bytes());
+    let mut pixels: Vec<u8> = payload.iter().flat_map(|byte| [byte >> 6, byte >> 4 & 3, byte >> 2 & 3, byte & 3]).collect();
+    let (width, height) = (128, pixels.len().div_ceil(128) as u16);
+    pixels.resize(width as usize * height as usize, 0);
+
+    let mut image = Vec::new();
+    let palette = [0, 0, 0, 85, 85, 85, 170, 170, 170, 255, 255, 255];
+    let mut encoder = gif::Encoder::new(&mut image, width, height, &palette).unwrap();
+    let frame = gif::Frame { width, height, buffer: Cow::Owned(pixels), ..gif::Frame::default() };
+    encoder.write_frame(&frame).unwrap();
+    drop(encoder);
+    return image;
+}
+
+#[test]
+fn cartridge_with_byte_literals() {
+    let image = cartridge("# byte literals only\n0x00 0xE0\n0x12 0x02\n");
+    assert_eq!(decode("game.gif", &image).unwrap(), vec![0x00, 0xE0, 0x12, 0x02]);
+}
+
+#[test]
+fn cartridge_with_octo_source() {
+    let error = decode("game.gif", &cartridge(": main\n  clear\n  loop again\n")).unwrap_err();
+    assert!(matches!(error, RomError::Cartridge(_)));
+    assert!(error.to_string().contains("only cartridges made of byte literals can be run"), "{}", error);
+}
+
+#[test]
+fn oversized_rom() {
+    let mut memory = Memory::new(Platform::OriginalChip8.memory_map());
+    assert!(memory.load_rom(&[0; 3584]).is_ok());
+    let error = memory.load_rom(&[0; 3585]).unwrap_err();
+    assert!(matches!(error, RomError::TooLarge { size: 3585, limit: 3584 }), "{}", error);
+}
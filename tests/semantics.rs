@@ -0,0 +1,245 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// Differential fuzzing of the CPU: random machine states and random instruction sequences run on `CPU`
+// (both backends) and on the reference model in tests/reference, and every register, the stack, the
+// screen and RAM have to agree after every instruction. The runs are deterministic, set PROPTEST_CASES
+// to try more cases than the default.
+
+mod reference;
+
+use proptest::prelude::*;
+use proptest::test_runner::Config;
+use proptest::test_runner::RngAlgorithm;
+use proptest::test_runner::TestCaseError;
+use proptest::test_runner::TestRng;
+use proptest::test_runner::TestRunner;
+
+use rs_8chip_interpret::interpreter::cpu::Backend;
+use rs_8chip_interpret::interpreter::cpu::CpuState;
+use rs_8chip_interpret::interpreter::cpu::CPU;
+use rs_8chip_interpret::interpreter::keypad::Keypad;
+use rs_8chip_interpret::interpreter::memory::Memory;
+use rs_8chip_interpret::interpreter::platform::Platform;
+use rs_8chip_interpret::interpreter::quirks::Quirks;
+use rs_8chip_interpret::interpreter::random;
+
+use reference::Model;
+use reference::Step;
+use reference::HEIGHT;
+use reference::RAM_SIZE;
+use reference::STACK_DEPTH;
+use reference::WIDTH;
+
+const STEPS: usize = 64;
+const CASES: u32 = 256;
+
+// Every instruction as its fixed bits and the mask of its operands.
+const TEMPLATES: [(u16, u16); 35] = [
+    (0x00E0, 0x0000), (0x00EE, 0x0000), (0x1000, 0x0FFF), (0x2000, 0x0FFF),
+    (0x3000, 0x0FFF), (0x4000, 0x0FFF), (0x5000, 0x0FF0), (0x6000, 0x0FFF),
+    (0x7000, 0x0FFF), (0x8000, 0x0FF0), (0x8001, 0x0FF0), (0x8002, 0x0FF0),
+    (0x8003, 0x0FF0), (0x8004, 0x0FF0), (0x8005, 0x0FF0), (0x8006, 0x0FF0),
+    (0x8007, 0x0FF0), (0x800E, 0x0FF0), (0x9000, 0x0FF0), (0xA000, 0x0FFF),
+    (0xB000, 0x0FFF), (0xC000, 0x0FFF), (0xD000, 0x0FFF), (0xE09E, 0x0F00),
+    (0xE0A1, 0x0F00), (0xF007, 0x0F00), (0xF00A, 0x0F00), (0xF015, 0x0F00),
+    (0xF018, 0x0F00), (0xF01E, 0x0F00), (0xF029, 0x0F00), (0xF030, 0x0F00),
+    (0xF033, 0x0F00), (0xF055, 0x0F00), (0xF065, 0x0F00),
+];
+
+#[derive(Clone, Debug)]
+struct Case {
+    quirks: reference::Quirks,
+    ram: Vec<u8>,
+    screen: Vec<bool>,
+    v: [u8; 16],
+    i: u16,
+    pc: u16,
+    stack: Vec<u16>,
+    timers: (u8, u8),
+    keys: Vec<u16>,
+}
+
+fn opcode() -> impl Strategy<Value = u16> {
+    return (0..TEMPLATES.len(), any::<u16>()).prop_map(|(template, operands)| {
+        let (fixed, mask) = TEMPLATES[template];
+        fixed | operands & mask
+    });
+}
+
+fn even_address() -> impl Strategy<Value = u16> {
+    return (0..RAM_SIZE as u16 / 2).prop_map(|address| address * 2);
+}
+
+fn case() -> impl Strategy<Value = Case> {
+    let quirks = any::<[bool; 5]>().prop_map(|[vf_reset, memory, shift, jump, clip]| {
+        reference::Quirks { vf_reset, memory, shift, jump, clip }
+    });
+    // RAM is all instructions, so jumps anywhere keep running, and doubles as sprite and register data.
+    let ram = prop::collection::vec(opcode(), RAM_SIZE / 2)
+        .prop_map(|opcodes| opcodes.iter().flat_map(|opcode| opcode.to_be_bytes()).collect::<Vec<u8>>());
+    let screen = prop::collection::vec(any::<bool>(), WIDTH * HEIGHT);
+    let registers = (any::<[u8; 16]>(), 0..RAM_SIZE as u16, even_address(), any::<(u8, u8)>());
+    let stack = prop::collection::vec(even_address(), 0..=STACK_DEPTH);
+    let keys = prop::collection::vec(prop_oneof![Just(0u16), (0..16u16).prop_map(|key| 1 << key), any::<u16>()], STEPS);
+    return (quirks, ram, screen, registers, stack, keys).prop_map(|(quirks, ram, screen, (v, i, pc, timers), stack, keys)| {
+        Case { quirks, ram, screen, v, i, pc, stack, timers, keys }
+    });
+}
+
+fn model(case: &Case) -> Model {
+    let mut screen = [[false; WIDTH]; HEIGHT];
+    for (index, pixel) in case.screen.iter().enumerate() {
+        screen[index / WIDTH][index % WIDTH] = *pixel;
+    }
+    return Model {
+        v: case.v,
+        i: case.i,
+        pc: case.pc,
+        stack: case.stack.clone(),
+        delay_timer: case.timers.0,
+        sound_timer: case.timers.1,
+        ram: case.ram.clone(),
+        screen,
+        quirks: case.quirks,
+    };
+}
+
+fn machine(case: &Case, model: &Model, backend: Backend) -> (CPU, Memory) {
+    // 4K of RAM that wraps around and no interpreter region, like the model.
+    let map = Platform::ModernChip8.memory_map();
+    let mut memory = Memory::new(map);
+    memory.set_ram(&case.ram);
+
+    let mut quirks = Quirks::new();
+    quirks.vf_reset = case.quirks.vf_reset;
+    quirks.memory = case.quirks.memory;
+    quirks.shift = case.quirks.shift;
+    quirks.jump = case.quirks.jump;
+    quirks.clip = case.quirks.clip;
+    quirks.display_wait = false;
+
+    let mut cpu = CPU::new(&map);
+    cpu.set_quirks(quirks);
+    cpu.set_stack(Some(STACK_DEPTH), false);
+    cpu.set_backend(backend);
+    cpu.set_random_source(random::from_name("default", 0).unwrap());
+    cpu.set_state(&CpuState {
+        buffer: model.screen,
+        pc: model.pc,
+        i: model.i,
+        sp: model.stack.len() as u16,
+        stack: model.stack.clone(),
+        v: model.v,
+        delay_timer: model.delay_timer,
+        sound_timer: model.sound_timer,
+    }).unwrap();
+    return (cpu, memory);
+}
+
+// Lists every difference between the CPU and the model.
+fn differences(state: &CpuState, ram: &[u8], model: &Model) -> Vec<String> {
+    let mut differences = Vec::new();
+    if state.pc != model.pc {
+        differences.push(format!("pc {:04X}, expected {:04X}", state.pc, model.pc));
+    }
+    if state.i != model.i {
+        differences.push(format!("I {:04X}, expected {:04X}", state.i, model.i));
+    }
+    for register in 0..16 {
+        if state.v[register] != model.v[register] {
+            differences.push(format!("V{:X} {:02X}, expected {:02X}", register, state.v[register], model.v[register]));
+        }
+    }
+    if state.stack != model.stack {
+        differences.push(format!("stack {:04X?}, expected {:04X?}", state.stack, model.stack));
+    }
+    if (state.delay_timer, state.sound_timer) != (model.delay_timer, model.sound_timer) {
+        differences.push(format!("timers {:?}, expected {:?}", (state.delay_timer, state.sound_timer), (model.delay_timer, model.sound_timer)));
+    }
+    if state.buffer != model.screen {
+        differences.push("screen".to_string());
+    }
+    for (address, (byte, expected)) in ram.iter().zip(model.ram.iter()).enumerate() {
+        if byte != expected {
+            differences.push(format!("RAM[{:03X}] {:02X}, expected {:02X}", address, byte, expected));
+        }
+    }
+    return differences;
+}
+
+fn check(case: &Case, backend: Backend) -> Result<(), TestCaseError> {
+    let mut model = model(case);
+    let (mut cpu, mut memory) = machine(case, &model, backend);
+    let mut keypad = Keypad::new();
+
+    for (step, keys) in case.keys.iter().enumerate() {
+        let opcode = model.opcode();
+        // Jumps into the middle of an instruction can land on anything.
+        if !reference::is_valid(opcode) {
+            break;
+        }
+        keypad.set_state(*keys);
+        let pc = model.pc;
+        let result = cpu.run(&mut memory, &mut keypad, 1);
+        let expected = model.step(*keys);
+        let context = format!("step {}, {:04X} at {:04X}, keys {:04X}", step, opcode, pc, keys);
+
+        if (expected == Step::Fault) != result.is_err() {
+            return Err(TestCaseError::fail(format!("{}: fault {:?}, expected {:?}", context, result.err(), expected)));
+        }
+        if expected == Step::Fault {
+            break;
+        }
+        let state = cpu.get_state();
+        if let Step::Random { x, mask } = expected {
+            if state.v[x] & !mask != 0 {
+                return Err(TestCaseError::fail(format!("{}: V{:X} {:02X} isn't masked with {:02X}", context, x, state.v[x], mask)));
+            }
+            model.v[x] = state.v[x];
+        }
+        let differences = differences(&state, memory.get_ram(), &model);
+        if !differences.is_empty() {
+            return Err(TestCaseError::fail(format!("{}: {}", context, differences.join(", "))));
+        }
+    }
+    return Ok(());
+}
+
+fn fuzz(backend: Backend) {
+    let cases = std::env::var("PROPTEST_CASES").ok().and_then(|cases| cases.parse().ok()).unwrap_or(CASES);
+    let config = Config { failure_persistence: None, ..Config::with_cases(cases) };
+    let mut runner = TestRunner::new_with_rng(config, TestRng::deterministic_rng(RngAlgorithm::ChaCha));
+    if let Err(error) = runner.run(&case(), |case| check(&case, backend)) {
+        panic!("{}", error);
+    }
+}
+
+#[test]
+fn interpreter_matches_reference() {
+    fuzz(Backend::Interpreter);
+}
+
+#[test]
+fn blocks_match_reference() {
+    fuzz(Backend::Blocks);
+}
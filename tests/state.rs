@@ -0,0 +1,89 @@
+// MIT License
+// 
+// Copyright (c) 2023 LumenTuoma
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// UTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// Save states only load into a CPU with the same kind of stack, SP would be meaningless otherwise.
+
+mod common;
+
+use rs_8chip_interpret::interpreter::cpu::CPU;
+use rs_8chip_interpret::interpreter::keypad::Keypad;
+use rs_8chip_interpret::interpreter::memory::Memory;
+use rs_8chip_interpret::interpreter::state;
+
+const ROM: [u8; 8] = [
+    0x22, 0x06, // call 0x206
+    0x12, 0x02, // jump 0x202
+    0x00, 0x00,
+    0x12, 0x06, // jump 0x206, inside the subroutine
+];
+
+// Runs until the ROM sits in its subroutine, one return address deep.
+fn machine(stack_in_ram: bool) -> (CPU, Memory) {
+    let (mut cpu, mut memory) = common::machine(&ROM);
+    cpu.set_stack(Some(12), stack_in_ram);
+    cpu.run(&mut memory, &mut Keypad::new(), 10).unwrap();
+    return (cpu, memory);
+}
+
+fn state_file(name: &str) -> String {
+    return std::env::temp_dir().join(format!("rs-8chip-{}-{}.state", name, std::process::id())).to_string_lossy().to_string();
+}
+
+#[test]
+fn round_trip() {
+    for stack_in_ram in [false, true] {
+        let path = state_file(&format!("round-trip-{}", stack_in_ram));
+        let (cpu, memory) = machine(stack_in_ram);
+        state::save(&path, &cpu, &memory);
+
+        let (mut loaded, mut loaded_memory) = machine(stack_in_ram);
+        loaded.reset();
+        state::load(&path, &mut loaded, &mut loaded_memory).unwrap();
+        assert!(loaded.get_state() == cpu.get_state());
+        assert!(loaded_memory.get_ram() == memory.get_ram());
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn other_stack_mode_is_rejected() {
+    for stack_in_ram in [false, true] {
+        let path = state_file(&format!("other-mode-{}", stack_in_ram));
+        let (cpu, memory) = machine(stack_in_ram);
+        state::save(&path, &cpu, &memory);
+
+        let (mut other, mut other_memory) = machine(!stack_in_ram);
+        let (before, ram) = (other.get_state(), other_memory.get_ram().to_vec());
+        assert!(state::load(&path, &mut other, &mut other_memory).is_err());
+        assert!(other.get_state() == before);
+        assert!(other_memory.get_ram() == ram.as_slice());
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn stack_pointer_above_the_stack_is_rejected() {
+    let (mut cpu, _) = machine(true);
+    let mut state = cpu.get_state();
+    state.sp = 0xF00;
+    assert!(cpu.set_state(&state).is_err());
+}